@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const MAX_CODE: u16 = 4096;
+
+pub struct GifWriter<W: Write> {
+    writer: W,
+    width: u16,
+    height: u16,
+    min_code_size: u8,
+}
+
+impl<W: Write> GifWriter<W> {
+    pub fn new(mut writer: W, width: u16, height: u16, palette: &[[u8; 3]]) -> io::Result<GifWriter<W>> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "GIF: palette must have 1 to 256 colors"));
+        }
+        let depth = (palette.len().next_power_of_two().trailing_zeros() as u8).max(1);
+
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        writer.write_all(&[0x80 | ((depth - 1) << 4) | (depth - 1), 0, 0])?;
+        for i in 0..(1usize << depth) {
+            writer.write_all(&palette.get(i).copied().unwrap_or([0, 0, 0]))?;
+        }
+        // Loop the animation forever
+        writer.write_all(&[0x21, 0xFF, 0x0B])?;
+        writer.write_all(b"NETSCAPE2.0")?;
+        writer.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(GifWriter {
+            writer,
+            width,
+            height,
+            min_code_size: depth.max(2),
+        })
+    }
+
+    pub fn write_frame(&mut self, pixels: &[u8], delay_centiseconds: u16) -> io::Result<()> {
+        if pixels.len() != usize::from(self.width) * usize::from(self.height) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "GIF: frame size does not match image size"));
+        }
+        self.writer.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.writer.write_all(&delay_centiseconds.to_le_bytes())?;
+        self.writer.write_all(&[0x00, 0x00])?;
+
+        self.writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.writer.write_all(&self.width.to_le_bytes())?;
+        self.writer.write_all(&self.height.to_le_bytes())?;
+        self.writer.write_all(&[0x00, self.min_code_size])?;
+
+        let data = lzw_encode(pixels, self.min_code_size);
+        for block in data.chunks(255) {
+            self.writer.write_all(&[block.len() as u8])?;
+            self.writer.write_all(block)?;
+        }
+        self.writer.write_all(&[0x00])
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&[0x3B])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= u32::from(code) << self.bits;
+        self.bits += u32::from(size);
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code = clear_code + 1;
+    let mut output = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+
+    output.write(clear_code, code_size);
+    let Some((&first, rest)) = pixels.split_first() else {
+        output.write(end_code, code_size);
+        return output.finish();
+    };
+
+    let mut prefix = u16::from(first);
+    for &pixel in rest {
+        if let Some(&code) = dictionary.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        output.write(prefix, code_size);
+        if next_code < MAX_CODE {
+            if next_code == 1 << code_size {
+                code_size += 1;
+            }
+            dictionary.insert((prefix, pixel), next_code);
+            next_code += 1;
+        } else {
+            output.write(clear_code, code_size);
+            dictionary.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+        }
+        prefix = u16::from(pixel);
+    }
+    output.write(prefix, code_size);
+    if next_code == 1 << code_size && code_size < 12 {
+        code_size += 1;
+    }
+    output.write(end_code, code_size);
+    output.finish()
+}
@@ -0,0 +1,433 @@
+use std::ops::{Bound, RangeBounds};
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct IntVar(usize);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Relation { Eq, Le, Ge }
+
+pub struct IntVarInfo {
+    pub name: String,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+pub struct LinearConstraint {
+    pub terms: Vec<(i64, IntVar)>,
+    pub relation: Relation,
+    pub bound: i64,
+}
+
+pub struct IntProblem {
+    variables: Vec<IntVarInfo>,
+    constraints: Vec<LinearConstraint>,
+    objective: Option<Vec<(i64, IntVar)>>,
+}
+
+impl IntProblem {
+    pub fn new() -> IntProblem {
+        IntProblem {
+            variables: Vec::new(),
+            constraints: Vec::new(),
+            objective: None,
+        }
+    }
+
+    pub fn add_var(&mut self, name: &str, domain: impl RangeBounds<i64>) -> IntVar {
+        let min = match domain.start_bound() {
+            Bound::Included(&min) => Some(min),
+            Bound::Excluded(&min) => Some(min + 1),
+            Bound::Unbounded => None,
+        };
+        let max = match domain.end_bound() {
+            Bound::Included(&max) => Some(max),
+            Bound::Excluded(&max) => Some(max - 1),
+            Bound::Unbounded => None,
+        };
+        self.variables.push(IntVarInfo { name: name.to_string(), min, max });
+        IntVar(self.variables.len() - 1)
+    }
+
+    pub fn constrain(&mut self, terms: &[(i64, IntVar)], relation: Relation, bound: i64) {
+        self.constraints.push(LinearConstraint {
+            terms: terms.to_vec(),
+            relation,
+            bound,
+        });
+    }
+
+    pub fn minimize(&mut self, terms: &[(i64, IntVar)]) {
+        self.objective = Some(terms.to_vec());
+    }
+
+    pub fn variables(&self) -> &[IntVarInfo] {
+        &self.variables
+    }
+
+    pub fn constraints(&self) -> &[LinearConstraint] {
+        &self.constraints
+    }
+
+    pub fn objective(&self) -> Option<&[(i64, IntVar)]> {
+        self.objective.as_deref()
+    }
+}
+
+impl Default for IntProblem {
+    fn default() -> Self {
+        IntProblem::new()
+    }
+}
+
+pub struct IntSolution {
+    values: Vec<i64>,
+}
+
+impl IntSolution {
+    pub fn get(&self, var: IntVar) -> i64 {
+        self.values[var.0]
+    }
+
+    pub fn evaluate(&self, terms: &[(i64, IntVar)]) -> i64 {
+        terms.iter().map(|&(coefficient, var)| coefficient * self.get(var)).sum()
+    }
+}
+
+pub trait IntSolver {
+    fn solve(&self, problem: &IntProblem) -> Result<Option<IntSolution>, String>;
+}
+
+pub fn default_int_solver() -> Box<dyn IntSolver> {
+    #[cfg(feature = "z3")]
+    return Box::new(Z3Solver);
+    #[cfg(not(feature = "z3"))]
+    return Box::new(BranchAndBound::new());
+}
+
+pub struct BranchAndBound {
+    pub node_limit: u64,
+}
+
+impl BranchAndBound {
+    pub fn new() -> BranchAndBound {
+        BranchAndBound { node_limit: 10_000_000 }
+    }
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        BranchAndBound::new()
+    }
+}
+
+type Domains = Vec<(i128, i128)>;
+
+struct BranchSearch<'a> {
+    problem: &'a IntProblem,
+    node_limit: u64,
+    nodes: u64,
+    best: Option<(Vec<i64>, i128)>,
+}
+
+impl IntSolver for BranchAndBound {
+    fn solve(&self, problem: &IntProblem) -> Result<Option<IntSolution>, String> {
+        let mut domains: Domains = Vec::new();
+        for info in problem.variables() {
+            match (info.min, info.max) {
+                (Some(min), Some(max)) => domains.push((min.into(), max.into())),
+                _ => {
+                    return Err(format!("Branch and bound requires a finite domain for variable {}", info.name));
+                }
+            }
+        }
+
+        let mut search = BranchSearch {
+            problem,
+            node_limit: self.node_limit,
+            nodes: 0,
+            best: None,
+        };
+        search.branch(domains)?;
+        Ok(search.best.map(|(values, _)| IntSolution { values }))
+    }
+}
+
+impl<'a> BranchSearch<'a> {
+    fn branch(&mut self, mut domains: Domains) -> Result<(), String> {
+        self.nodes += 1;
+        if self.nodes > self.node_limit {
+            return Err(format!("Branch and bound exceeded node limit of {}", self.node_limit));
+        }
+
+        if !self.propagate(&mut domains) {
+            return Ok(());
+        }
+
+        if let (Some((_, best_cost)), Some(objective)) = (&self.best, self.problem.objective()) {
+            if Self::sum_bounds(objective, &domains).0 >= *best_cost {
+                return Ok(());
+            }
+        }
+
+        let Some(index) = domains.iter().position(|(min, max)| min < max) else {
+            let values: Vec<i64> = domains.iter().map(|&(value, _)| value as i64).collect();
+            let cost = match self.problem.objective() {
+                Some(objective) => Self::sum_bounds(objective, &domains).0,
+                None => 0,
+            };
+            self.best = Some((values, cost));
+            return Ok(());
+        };
+
+        let ascending = self.problem.objective()
+            .and_then(|objective| objective.iter().find(|(_, var)| var.0 == index))
+            .is_none_or(|&(coefficient, _)| coefficient >= 0);
+        let (min, max) = domains[index];
+        let mut value = if ascending { min } else { max };
+        while value >= min && value <= max {
+            let mut next = domains.clone();
+            next[index] = (value, value);
+            self.branch(next)?;
+            if self.best.is_some() && self.problem.objective().is_none() {
+                return Ok(());
+            }
+            value += if ascending { 1 } else { -1 };
+        }
+        Ok(())
+    }
+
+    fn propagate(&self, domains: &mut Domains) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for constraint in self.problem.constraints() {
+                let (sum_min, sum_max) = Self::sum_bounds(&constraint.terms, domains);
+                let bound: i128 = constraint.bound.into();
+                let check_le = constraint.relation != Relation::Ge;
+                let check_ge = constraint.relation != Relation::Le;
+                if (check_le && sum_min > bound) || (check_ge && sum_max < bound) {
+                    return false;
+                }
+
+                for &(coefficient, var) in constraint.terms.iter() {
+                    if coefficient == 0 {
+                        continue;
+                    }
+                    let coefficient: i128 = coefficient.into();
+                    let (min, max) = domains[var.0];
+                    let (term_min, term_max) = Self::term_bounds(coefficient, min, max);
+                    let (mut new_min, mut new_max) = (min, max);
+                    if check_le {
+                        // coefficient * var <= bound - (sum_min - term_min)
+                        let rest = bound - (sum_min - term_min);
+                        if coefficient > 0 {
+                            new_max = new_max.min(rest.div_euclid(coefficient));
+                        } else {
+                            new_min = new_min.max(Self::div_ceil(rest, coefficient));
+                        }
+                    }
+                    if check_ge {
+                        // coefficient * var >= bound - (sum_max - term_max)
+                        let rest = bound - (sum_max - term_max);
+                        if coefficient > 0 {
+                            new_min = new_min.max(Self::div_ceil(rest, coefficient));
+                        } else {
+                            new_max = new_max.min(Self::div_floor(rest, coefficient));
+                        }
+                    }
+                    if new_min > new_max {
+                        return false;
+                    } else if (new_min, new_max) != (min, max) {
+                        domains[var.0] = (new_min, new_max);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn sum_bounds(terms: &[(i64, IntVar)], domains: &Domains) -> (i128, i128) {
+        let mut sum_min = 0;
+        let mut sum_max = 0;
+        for &(coefficient, var) in terms {
+            let (min, max) = domains[var.0];
+            let (term_min, term_max) = Self::term_bounds(coefficient.into(), min, max);
+            sum_min += term_min;
+            sum_max += term_max;
+        }
+        (sum_min, sum_max)
+    }
+
+    fn term_bounds(coefficient: i128, min: i128, max: i128) -> (i128, i128) {
+        if coefficient >= 0 {
+            (coefficient * min, coefficient * max)
+        } else {
+            (coefficient * max, coefficient * min)
+        }
+    }
+
+    fn div_floor(a: i128, b: i128) -> i128 {
+        let quotient = a / b;
+        if (a % b != 0) && ((a < 0) != (b < 0)) { quotient - 1 } else { quotient }
+    }
+
+    fn div_ceil(a: i128, b: i128) -> i128 {
+        let quotient = a / b;
+        if (a % b != 0) && ((a < 0) == (b < 0)) { quotient + 1 } else { quotient }
+    }
+}
+
+#[cfg(feature = "z3")]
+pub struct Z3Solver;
+
+#[cfg(feature = "z3")]
+impl IntSolver for Z3Solver {
+    fn solve(&self, problem: &IntProblem) -> Result<Option<IntSolution>, String> {
+        use z3::ast::{Ast, Int};
+
+        let config = z3::Config::new();
+        let ctx = z3::Context::new(&config);
+        let optimize = z3::Optimize::new(&ctx);
+
+        let vars: Vec<Int> = problem.variables().iter()
+            .map(|info| Int::new_const(&ctx, info.name.as_str()))
+            .collect();
+        for (var, info) in vars.iter().zip(problem.variables()) {
+            if let Some(min) = info.min {
+                optimize.assert(&var.ge(&Int::from_i64(&ctx, min)));
+            }
+            if let Some(max) = info.max {
+                optimize.assert(&var.le(&Int::from_i64(&ctx, max)));
+            }
+        }
+
+        let to_sum = |terms: &[(i64, IntVar)]| {
+            let products: Vec<Int> = terms.iter()
+                .map(|&(coefficient, var)| Int::mul(&ctx, &[&Int::from_i64(&ctx, coefficient), &vars[var.0]]))
+                .collect();
+            if products.is_empty() {
+                Int::from_i64(&ctx, 0)
+            } else {
+                Int::add(&ctx, &products.iter().collect::<Vec<_>>())
+            }
+        };
+
+        for constraint in problem.constraints() {
+            let sum = to_sum(&constraint.terms);
+            let bound = Int::from_i64(&ctx, constraint.bound);
+            let condition = match constraint.relation {
+                Relation::Eq => sum._eq(&bound),
+                Relation::Le => sum.le(&bound),
+                Relation::Ge => sum.ge(&bound),
+            };
+            optimize.assert(&condition);
+        }
+
+        if let Some(objective) = problem.objective() {
+            optimize.minimize(&to_sum(objective));
+        }
+
+        match optimize.check(&[]) {
+            z3::SatResult::Sat => {
+                let model = optimize.get_model().ok_or("Z3: failed to get model")?;
+                let mut values = Vec::new();
+                for (var, info) in vars.iter().zip(problem.variables()) {
+                    let value = model.eval(var, true)
+                        .and_then(|value| value.as_i64())
+                        .ok_or(format!("Z3: failed to evaluate variable {}", info.name))?;
+                    values.push(value);
+                }
+                Ok(Some(IntSolution { values }))
+            }
+            z3::SatResult::Unsat => Ok(None),
+            z3::SatResult::Unknown => Err(format!(
+                "Z3: unknown result ({})",
+                optimize.get_reason_unknown().unwrap_or_default()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ten 0/1 variables whose doubled sum has to be odd: bounds propagation alone can not
+    // rule it out, so the search has to branch through the variables
+    fn odd_double_sum() -> IntProblem {
+        let mut problem = IntProblem::new();
+        let terms: Vec<(i64, IntVar)> = (0..10)
+            .map(|i| (2, problem.add_var(&format!("x{i}"), 0..=1)))
+            .collect();
+        problem.constrain(&terms, Relation::Eq, 11);
+        problem
+    }
+
+    #[test]
+    fn solves_feasible_problem() {
+        let mut problem = IntProblem::new();
+        let x = problem.add_var("x", 0..=10);
+        let y = problem.add_var("y", 0..11);
+        problem.constrain(&[(1, x), (1, y)], Relation::Eq, 10);
+        problem.constrain(&[(1, x), (-1, y)], Relation::Ge, 2);
+        problem.constrain(&[(1, x)], Relation::Le, 6);
+        let solution = BranchAndBound::new().solve(&problem).unwrap().unwrap();
+        assert_eq!((solution.get(x), solution.get(y)), (6, 4));
+    }
+
+    #[test]
+    fn detects_infeasible_problems() {
+        let mut problem = IntProblem::new();
+        let x = problem.add_var("x", 0..=10);
+        problem.constrain(&[(2, x)], Relation::Eq, 7);
+        assert!(BranchAndBound::new().solve(&problem).unwrap().is_none());
+
+        let mut problem = IntProblem::new();
+        let x = problem.add_var("x", 0..=10);
+        let y = problem.add_var("y", 0..=10);
+        problem.constrain(&[(1, x), (1, y)], Relation::Ge, 25);
+        assert!(BranchAndBound::new().solve(&problem).unwrap().is_none());
+
+        assert!(BranchAndBound::new().solve(&odd_double_sum()).unwrap().is_none());
+    }
+
+    #[test]
+    fn minimizes_objective() {
+        // The first claw machine of the puzzle 13 sample
+        let mut problem = IntProblem::new();
+        let a = problem.add_var("a", 0..=100);
+        let b = problem.add_var("b", 0..=100);
+        problem.constrain(&[(94, a), (22, b)], Relation::Eq, 8400);
+        problem.constrain(&[(34, a), (67, b)], Relation::Eq, 5400);
+        problem.minimize(&[(3, a), (1, b)]);
+        let solution = BranchAndBound::new().solve(&problem).unwrap().unwrap();
+        assert_eq!((solution.get(a), solution.get(b)), (80, 40));
+        assert_eq!(solution.evaluate(&[(3, a), (1, b)]), 280);
+
+        // Maximizing through a negative objective coefficient
+        let mut problem = IntProblem::new();
+        let x = problem.add_var("x", 0..=10);
+        let y = problem.add_var("y", 1..=10);
+        problem.constrain(&[(3, x), (2, y)], Relation::Le, 13);
+        problem.minimize(&[(-1, x)]);
+        let solution = BranchAndBound::new().solve(&problem).unwrap().unwrap();
+        assert_eq!((solution.get(x), solution.get(y)), (3, 1));
+    }
+
+    #[test]
+    fn requires_finite_domains() {
+        let mut problem = IntProblem::new();
+        problem.add_var("x", 0..);
+        assert!(BranchAndBound::new().solve(&problem).is_err());
+    }
+
+    #[test]
+    fn stops_at_node_limit() {
+        let solver = BranchAndBound { node_limit: 5 };
+        assert_eq!(
+            solver.solve(&odd_double_sum()).err(),
+            Some("Branch and bound exceeded node limit of 5".to_string())
+        );
+    }
+}
@@ -1,7 +1,11 @@
+mod gif;
 mod grid;
+mod int_solver;
 mod path_find;
 mod project;
 
+pub use gif::*;
 pub use grid::*;
+pub use int_solver::*;
 pub use path_find::*;
-pub use project::get_data_path;
+pub use project::{get_data_path, split_options};
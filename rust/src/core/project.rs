@@ -1,5 +1,26 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 pub fn get_data_path(data_path: impl AsRef<Path>) -> PathBuf {
     return Path::new("../../advent-of-code-data/2024").join(data_path);
 }
+
+// Splits arguments into positional ones and "--name" options, taking the next argument
+// as the value of the options listed in `value_options`
+pub fn split_options<'a>(args: &'a [String], value_options: &[&str]) -> (Vec<&'a str>, HashMap<&'a str, &'a str>) {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    let mut iter = args.iter().map(|arg| arg.as_str());
+    while let Some(arg) = iter.next() {
+        if value_options.contains(&arg) {
+            if let Some(value) = iter.next() {
+                options.insert(arg, value);
+            }
+        } else if arg.starts_with("--") {
+            options.insert(arg, "");
+        } else {
+            positional.push(arg);
+        }
+    }
+    (positional, options)
+}
@@ -1,7 +1,6 @@
-use core::get_data_path;
+use core::{default_int_solver, get_data_path, IntProblem, IntSolver, Relation};
 use regex::Regex;
 use std::fs::read_to_string;
-use z3::{self, ast::Ast};
 
 fn main() {
     use std::time::Instant;
@@ -15,32 +14,10 @@ fn basic() {
     let input = read_to_string(get_data_path("input/puzzle13.txt")).unwrap();
     let machines = ClawMachine::parse(&input);
 
-    let config = z3::Config::new();
-    let ctx = z3::Context::new(&config);
-
-    let solver = ClawSolver::new(&ctx);
+    let solver = default_int_solver();
     let mut total_cost: i64 = 0;
     for (i, machine) in machines.iter().enumerate() {
-        let answers = solver.find_presses(machine);
-        match answers.len() {
-            0 => {
-                println!("#{}: no answer", i + 1);
-            }
-            1 => {
-                let (a, b) = *answers.first().unwrap();
-                let cost = ClawMachine::answer_cost((a, b));
-                println!("#{}: single answer, cost = {a} * 3 + {b} = {cost}", i + 1);
-                total_cost += cost;
-            }
-            _ => {
-                let (a, b) = *answers.iter()
-                    .min_by_key(|answer| ClawMachine::answer_cost(**answer))
-                    .unwrap();
-                let cost = ClawMachine::answer_cost((a, b));
-                println!("#{}: multiple answers, cost = {a} * 3 + {b} = {cost}", i + 1);
-                total_cost += cost;
-            }
-        }
+        total_cost += report_cheapest_presses(i, machine, solver.as_ref());
     }
 
     println!("Total claw machine cost (basic): {total_cost}");
@@ -57,37 +34,33 @@ fn advanced() {
         })
         .collect();
 
-    let config = z3::Config::new();
-    let ctx = z3::Context::new(&config);
-
-    let solver = ClawSolver::new(&ctx);
+    let solver = default_int_solver();
     let mut total_cost: i64 = 0;
     for (i, machine) in machines.iter().enumerate() {
-        let answers = solver.find_presses(machine);
-        match answers.len() {
-            0 => {
-                println!("#{}: no answer", i + 1);
-            }
-            1 => {
-                let (a, b) = *answers.first().unwrap();
-                let cost = ClawMachine::answer_cost((a, b));
-                println!("#{}: single answer, cost = {a} * 3 + {b} = {cost}", i + 1);
-                total_cost += cost;
-            }
-            _ => {
-                let (a, b) = *answers.iter()
-                    .min_by_key(|answer| ClawMachine::answer_cost(**answer))
-                    .unwrap();
-                let cost = ClawMachine::answer_cost((a, b));
-                println!("#{}: multiple answers, cost = {a} * 3 + {b} = {cost}", i + 1);
-                total_cost += cost;
-            }
-        }
+        total_cost += report_cheapest_presses(i, machine, solver.as_ref());
     }
 
     println!("Total claw machine cost (advanced): {total_cost}");
 }
 
+fn report_cheapest_presses(i: usize, machine: &ClawMachine, solver: &dyn IntSolver) -> i64 {
+    match machine.find_cheapest_presses(solver) {
+        Ok(Some((a, b))) => {
+            let cost = ClawMachine::answer_cost((a, b));
+            println!("#{}: answer, cost = {a} * 3 + {b} = {cost}", i + 1);
+            cost
+        }
+        Ok(None) => {
+            println!("#{}: no answer", i + 1);
+            0
+        }
+        Err(err) => {
+            println!("#{}: failed to solve: {err}", i + 1);
+            0
+        }
+    }
+}
+
 struct ClawMachine {
     a_x: i64,
     a_y: i64,
@@ -121,72 +94,29 @@ impl ClawMachine {
     fn answer_cost((a, b): (i64, i64)) -> i64 {
         a * 3 + b
     }
-}
-
-struct ClawSolver<'a> {
-    ctx: &'a z3::Context,
-    var_a: z3::ast::Int<'a>,
-    var_b: z3::ast::Int<'a>,
-}
-
-impl<'a> ClawSolver<'a> {
-    fn new(ctx: &z3::Context) -> ClawSolver {
-        use z3::ast::Int;
-
-        let a = Int::new_const(&ctx, "a");
-        let b = Int::new_const(&ctx, "b");
 
-        ClawSolver { ctx, var_a: a, var_b: b }
+    // A button that does not move the claw along an axis places no bound on that axis, and one
+    // that does not move it at all is never worth pressing
+    fn max_presses((offset_x, offset_y): (i64, i64), (prize_x, prize_y): (i64, i64)) -> i64 {
+        [(prize_x, offset_x), (prize_y, offset_y)].into_iter()
+            .filter(|&(_, offset)| offset != 0)
+            .map(|(prize, offset)| prize / offset)
+            .min()
+            .unwrap_or(0)
     }
 
-    fn find_presses(&self, machine: &ClawMachine) -> Vec<(i64, i64)> {
-        use z3::ast::{Bool, Int};
+    fn find_cheapest_presses(&self, solver: &dyn IntSolver) -> Result<Option<(i64, i64)>, String> {
+        let max_a = Self::max_presses((self.a_x, self.a_y), (self.prize_x, self.prize_y));
+        let max_b = Self::max_presses((self.b_x, self.b_y), (self.prize_x, self.prize_y));
 
-        let ctx = self.ctx;
-        let a = &self.var_a;
-        let b = &self.var_b;
+        let mut problem = IntProblem::new();
+        let a = problem.add_var("a", 0..=max_a);
+        let b = problem.add_var("b", 0..=max_b);
+        problem.constrain(&[(self.a_x, a), (self.b_x, b)], Relation::Eq, self.prize_x);
+        problem.constrain(&[(self.a_y, a), (self.b_y, b)], Relation::Eq, self.prize_y);
+        problem.minimize(&[(3, a), (1, b)]);
 
-        let a_x = Int::from_i64(&ctx, machine.a_x);
-        let a_y = Int::from_i64(&ctx, machine.a_y);
-        let b_x = Int::from_i64(&ctx, machine.b_x);
-        let b_y = Int::from_i64(&ctx, machine.b_y);
-        let target_x = Int::from_i64(&ctx, machine.prize_x);
-        let target_y = Int::from_i64(&ctx, machine.prize_y);
-
-        let min = Int::from_i64(&ctx, 0);
-
-        let problem = Bool::and(&ctx, &[
-            &a.ge(&min),
-            &b.ge(&min),
-            &(a.clone() * a_x + b.clone() * b_x)._eq(&target_x),
-            &(a.clone() * a_y + b.clone() * b_y)._eq(&target_y),
-        ]);
-
-        let solver = z3::Solver::new(&ctx);
-        solver.assert(&problem);
-
-        let mut answers: Vec<(i64, i64)> = Vec::new();
-        loop {
-            match solver.check() {
-                z3::SatResult::Sat => {
-                    let model = solver.get_model().unwrap();
-                    let a_value = model.eval(a, true)
-                        .unwrap()
-                        .as_i64()
-                        .unwrap();
-                    let b_value = model.eval(b, true)
-                        .unwrap()
-                        .as_i64()
-                        .unwrap();
-                    answers.push((a_value, b_value));
-                    solver.assert( &a._eq(&Int::from_i64(&ctx, a_value)).not());
-                    solver.assert(&b._eq(&Int::from_i64(&ctx, b_value)).not());
-                }
-                _ => {
-                    break;
-                }
-            }
-        }
-        return answers;
+        let solution = solver.solve(&problem)?;
+        return Ok(solution.map(|solution| (solution.get(a), solution.get(b))));
     }
 }
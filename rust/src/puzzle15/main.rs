@@ -1,226 +1,297 @@
-use core::{Grid, get_data_path};
-use std::{fs::{read_to_string, File}, io::{LineWriter, Write}};
+use core::{GifWriter, Grid, get_data_path, split_options};
+use std::{env, fs::{read_to_string, File}, io::{BufWriter, LineWriter, Write}};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use solver::SolveTarget;
+use warehouse::Warehouse;
+
+mod solver;
+mod validate;
+mod warehouse;
 
 fn main() {
-    use std::time::Instant;
-    let before = Instant::now();
-    basic();
-    advanced();
-    println!("Elapsed time: {:.2?}", before.elapsed());
+    let args: Vec<String> = env::args().collect();
+    let (positional, options) = split_options(&args[1..], &[
+        "--widen", "--frames", "--gif", "--scale", "--every", "--delay", "--first-move", "--at",
+        "--target", "--target-gps", "--max-nodes", "--out",
+    ]);
+    if options.is_empty() {
+        use std::time::Instant;
+        let before = Instant::now();
+        if let Err(report) = basic().and_then(|_| advanced()) {
+            eprintln!("{report}");
+            exit(1);
+        }
+        println!("Elapsed time: {:.2?}", before.elapsed());
+    } else {
+        let input_path = positional.first()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| get_data_path("input/puzzle15.txt"));
+        let result = if options.contains_key("--lint") {
+            lint(&input_path)
+        } else if options.contains_key("--solve") {
+            solve(&input_path, &options)
+        } else {
+            explore(&input_path, &options)
+        };
+        if let Err(err) = result {
+            eprintln!("Error: {err}");
+            exit(1);
+        }
+    }
 }
 
-fn basic() {
-    let input = read_to_string(get_data_path("input/puzzle15.txt")).unwrap();
-    let (mut grid, steps) = parse_input(&input);
+fn basic() -> Result<(), String> {
+    let input_path = get_data_path("input/puzzle15.txt");
+    let input = read_to_string(&input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))?;
+    let (grid, steps) = parse_input(&input, true)?;
 
-    let mut position = grid.find(&'@').unwrap();
+    let mut warehouse = Warehouse::from_grid(&grid)?;
     for step in steps.chars() {
-        let direction = direction_from_step(step);
-        position = simulate_step_basic(&mut grid, position, direction);
-    }
-
-    let mut writer = LineWriter::new(
-        File::create(get_data_path("output/puzzle15_after.txt")).unwrap()
-    );
-    for line in grid.lines() {
-        writer.write_all(line.as_bytes()).unwrap();
+        warehouse.apply(step);
     }
+    write_warehouse(&warehouse, &get_data_path("output/puzzle15_after.txt"))?;
 
-    let gps_total = compute_gps_sum(&grid);
+    let gps_total = warehouse.gps_sum();
     println!("GPS total (basic): {gps_total}");
+    Ok(())
 }
 
-fn simulate_step_basic(grid: &mut Grid<char>, position: Vector, direction: Vector) -> Vector {
-    let mut current = position;
-    let mut move_count = 0;
-    loop {
-        let next = (
-            current.0 + direction.0,
-            current.1 + direction.1,
-        );
-        match grid.get(next) {
-            Some('#') | None => {
-                return position;
-            }
-            Some('O') => {
-                move_count += 1;
-            }
-            Some('.') => {
-                let next_once = (
-                    position.0 + direction.0,
-                    position.1 + direction.1,
-                );
-                grid.set(position, '.');
-                grid.set(next_once, '@');
-                if move_count > 0 {
-                    grid.set(next, 'O');
-                }
-                return next_once;
-            }
-            _ => {}
-        }
-        current = next;
+fn advanced() -> Result<(), String> {
+    let input_path = get_data_path("input/puzzle15.txt");
+    let input = read_to_string(&input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))?;
+    let (initial_grid, steps) = parse_input(&input, true)?;
+
+    let grid = widen_grid(&initial_grid, 2);
+    let mut warehouse = Warehouse::from_grid(&grid)?;
+    for step in steps.chars() {
+        warehouse.apply(step);
     }
+    write_warehouse(&warehouse, &get_data_path("output/puzzle15_wide.txt"))?;
+
+    let gps_total = warehouse.gps_sum();
+    println!("GPS total (advanced): {gps_total}");
+    Ok(())
 }
 
-fn advanced() {
-    let input_path = read_to_string(get_data_path("input/puzzle15.txt")).unwrap();
-    let (initial_grid, steps) = parse_input(&input_path);
+fn write_warehouse(warehouse: &Warehouse, path: &Path) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("Failed to create {}: {err}", path.display()))?;
+    let mut writer = LineWriter::new(file);
+    for line in warehouse.to_grid().lines() {
+        writer.write_all(line.as_bytes())
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    }
+    Ok(())
+}
 
-    let mut grid = Grid::empty(initial_grid.width() * 2, initial_grid.height());
+fn widen_grid(initial_grid: &Grid<char>, factor: i32) -> Grid<char> {
+    let mut grid = Grid::empty(initial_grid.width() * factor, initial_grid.height());
     for i in 0..initial_grid.width() {
         for j in 0..initial_grid.height() {
-            match initial_grid.get((i, j)) {
-                Some('@') => {
-                    grid.set((i * 2, j), '@');
-                    grid.set((i * 2 + 1, j), '.');
-                }
-                Some('O') => {
-                    grid.set((i * 2, j), '[');
-                    grid.set((i * 2 + 1, j), ']');
-                }
-                Some(other) => {
-                    grid.set((i * 2, j), other);
-                    grid.set((i * 2 + 1, j), other);
-                }
-                _ => {}
+            for k in 0..factor {
+                let value = match initial_grid.get((i, j)).unwrap() {
+                    '@' if k > 0 => '.',
+                    'O' if factor > 1 && k == 0 => '[',
+                    'O' if factor > 1 && k == factor - 1 => ']',
+                    'O' if factor > 1 => '=',
+                    other => other,
+                };
+                grid.set((i * factor + k, j), value);
             }
         }
     }
+    grid
+}
 
-    // let mut step_writer = LineWriter::new(
-    //     File::create(get_data_path("output/puzzle15_wide_log.txt")).unwrap()
-    // );
-
-    let mut position = grid.find(&'@').unwrap();
-    for step in steps.chars() {
-        let direction = direction_from_step(step);
-        position = simulate_step_advanced(&mut grid, position, direction);
-
-        // write!(&mut step_writer, "Step: {step}\n").unwrap();
-        // for line in grid.lines() {
-        //     step_writer.write_all(line.as_bytes()).unwrap();
-        // }
-        // step_writer.write_all(b"\n").unwrap();
-    }
+fn explore(input_path: &Path, options: &HashMap<&str, &str>) -> Result<(), String> {
+    let input = read_to_string(input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))?;
+    let (grid, steps) = parse_input(&input, true)?;
+    let grid = widen_grid(&grid, parse_widen(options)?);
+    let mut warehouse = Warehouse::from_grid(&grid)?;
 
-    let mut writer = LineWriter::new(
-        File::create(get_data_path("output/puzzle15_wide.txt")).unwrap()
-    );
-    for line in grid.lines() {
-        writer.write_all(line.as_bytes()).unwrap();
-    }
+    let parse_number = |name: &str, default: i32| -> Result<i32, String> {
+        match options.get(name) {
+            Some(value) => value.parse::<i32>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or(format!("Invalid {name} value: {value}")),
+            None => Ok(default),
+        }
+    };
+    let scale = parse_number("--scale", 4)?;
+    let every = parse_number("--every", 1)? as usize;
+    let delay = parse_number("--delay", 5)? as u16;
 
-    let gps_total = compute_gps_sum(&grid);
-    println!("GPS total (advanced): {gps_total}");
-}
+    let mut frame_writer = match options.get("--frames") {
+        Some(path) => Some(LineWriter::new(
+            File::create(path).map_err(|err| format!("Failed to create {path}: {err}"))?
+        )),
+        None => None,
+    };
+    let mut gif_writer = match options.get("--gif") {
+        Some(path) => {
+            let file = File::create(path).map_err(|err| format!("Failed to create {path}: {err}"))?;
+            let (width, height, pixels) = warehouse.render_pixels(scale, &[]);
+            let mut gif = GifWriter::new(BufWriter::new(file), width, height, &warehouse::PALETTE)
+                .map_err(|err| format!("Failed to write {path}: {err}"))?;
+            gif.write_frame(&pixels, delay).map_err(|err| format!("Failed to write {path}: {err}"))?;
+            Some(gif)
+        }
+        None => None,
+    };
+    let io_error = |err: std::io::Error| format!("Failed to write output: {err}");
 
-fn simulate_step_advanced(grid: &mut Grid<char>, position: Vector, direction: Vector) -> Vector {
-    let next = (
-        position.0 + direction.0,
-        position.1 + direction.1,
-    );
-    match grid.get(next) {
-        Some('.') => {
-            grid.set(position, '.');
-            grid.set(next, '@');
-            return next;
+    if let Some(writer) = frame_writer.as_mut() {
+        writeln!(writer, "Initial state:").map_err(io_error)?;
+        for line in warehouse.to_grid().lines() {
+            writer.write_all(line.as_bytes()).map_err(io_error)?;
         }
-        Some('[' | ']') => {
-            if direction.0 != 0 && can_move_horizontal(grid, next, direction.0) {
-                move_horizontal(grid, next, direction.0);
-                grid.set(position, '.');
-                grid.set(next, '@');
-                return next;
-            } else if direction.1 != 0 && can_move_vertical(grid, next, direction.1) {
-                move_vertical(grid, next, direction.1);
-                grid.set(position, '.');
-                grid.set(next, '@');
-                return next;
-            } else {
-                return position;
+    }
+    for (i, step) in steps.chars().enumerate() {
+        let moved = warehouse.apply(step).moved.clone();
+        if let Some(writer) = frame_writer.as_mut() {
+            writeln!(writer, "\nStep {}: {step}", i + 1).map_err(io_error)?;
+            for line in warehouse.to_grid().lines() {
+                writer.write_all(line.as_bytes()).map_err(io_error)?;
             }
         }
-        _ => {
-            return position;
+        if let Some(gif) = gif_writer.as_mut() {
+            if (i + 1) % every == 0 || i + 1 == steps.len() {
+                let (_, _, pixels) = warehouse.render_pixels(scale, &moved);
+                gif.write_frame(&pixels, delay).map_err(io_error)?;
+            }
         }
     }
-}
+    if let Some(gif) = gif_writer {
+        gif.finish().map_err(io_error)?;
+    }
+    println!("GPS total: {}", warehouse.gps_sum());
 
-fn can_move_horizontal(grid: &Grid<char>, at: Vector, dx: i32) -> bool {
-    let (mut x, y) = at;
-    loop {
-        match grid.get((x, y)) {
-            Some('[' | ']') => {}
-            Some('.') => {
-                return true;
-            }
-            _ => {
-                return false;
+    if let Some(position) = options.get("--first-move") {
+        let at = parse_position(position)?;
+        let step_count = warehouse.history().len();
+        warehouse.seek(0);
+        let box_id = warehouse.box_at(at).ok_or(format!("No box at {position} in the initial layout"))?;
+        warehouse.seek(step_count);
+        match warehouse.first_move(box_id) {
+            Some(step) => {
+                let delta = &warehouse.history()[step];
+                println!("Box #{box_id} at {position} first moved at step {} ({})", step + 1, delta.step);
             }
+            None => println!("Box #{box_id} at {position} never moved"),
+        }
+    }
+
+    if let Some(at) = options.get("--at") {
+        let step = at.parse::<usize>().map_err(|_| format!("Invalid step number: {at}"))?;
+        warehouse.seek(step);
+        println!("State after step {}:", step.min(warehouse.history().len()));
+        for line in warehouse.to_grid().lines() {
+            print!("{line}");
         }
-        x += dx;
     }
+
+    Ok(())
 }
 
-fn move_horizontal(grid: &mut Grid<char>, at: Vector, dx: i32) {
-    let (mut x, y) = at;
-    let mut previous = grid.get((x, y)).unwrap();
-    x += dx;
-    loop {
-        let value = grid.get((x, y));
-        match value {
-            Some('[' | ']') => {
-                let part = value.unwrap();
-                grid.set((x, y), previous);
-                previous = part;
-            }
-            Some('.') => {
-                grid.set((x, y), previous);
-                return;
-            }
-            _ => {
-                return;
+fn lint(input_path: &Path) -> Result<(), String> {
+    let input = read_to_string(input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))?;
+    parse_input(&input, true)?;
+    println!("No problems found in {}", input_path.display());
+    Ok(())
+}
+
+fn solve(input_path: &Path, options: &HashMap<&str, &str>) -> Result<(), String> {
+    let widen = parse_widen(options)?;
+    let input = read_to_string(input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))?;
+    let grid = widen_grid(&parse_input(&input, true)?.0, widen);
+    let warehouse = Warehouse::from_grid(&grid)?;
+
+    let target = match (options.get("--target"), options.get("--target-gps")) {
+        (Some(path), None) => {
+            let target_input = read_to_string(path).map_err(|err| format!("Failed to read {path}: {err}"))?;
+            let target_grid = widen_grid(&parse_input(&target_input, false)?.0, widen);
+            if (target_grid.width(), target_grid.height()) != (grid.width(), grid.height()) {
+                return Err(format!("Target layout in {path} does not match the warehouse size"));
             }
+            SolveTarget::Layout(Warehouse::box_layout(&target_grid)?)
+        }
+        (None, Some(value)) => {
+            SolveTarget::GpsSum(value.parse::<i32>().map_err(|_| format!("Invalid --target-gps value: {value}"))?)
         }
-        x += dx;
+        _ => {
+            return Err("Specify exactly one of --target <layout file> or --target-gps <sum>".to_string());
+        }
+    };
+    let max_nodes = match options.get("--max-nodes") {
+        Some(value) => value.parse::<usize>().map_err(|_| format!("Invalid --max-nodes value: {value}"))?,
+        None => 2_000_000,
+    };
+
+    let moves = solver::solve(&warehouse, &target, max_nodes)?
+        .ok_or("The target cannot be reached from this warehouse")?;
+
+    let mut replay = Warehouse::from_grid(&grid)?;
+    for step in moves.chars() {
+        replay.apply(step);
+    }
+    if !target.is_satisfied(&replay) {
+        return Err("Replaying the found moves does not reach the target".to_string());
     }
-}
 
-fn can_move_vertical(grid: &Grid<char>, at: Vector, dy: i32) -> bool {
-    let value = grid.get(at);
-    match value {
-        Some('[' | ']') => {
-            let sign = if value.unwrap() == '[' { 1 } else { -1 };
-            return can_move_vertical(grid, (at.0, at.1 + dy), dy) &&
-                can_move_vertical(grid, (at.0 + sign, at.1 + dy), dy);
+    let mut puzzle: String = grid.lines().collect();
+    puzzle.push('\n');
+    let chars: Vec<char> = moves.chars().collect();
+    for line in chars.chunks(70) {
+        puzzle.extend(line);
+        puzzle.push('\n');
+    }
+    let summary = format!("Solved in {} moves, replay reaches GPS total {}", moves.len(), replay.gps_sum());
+    match options.get("--out") {
+        Some(path) => {
+            std::fs::write(path, puzzle).map_err(|err| format!("Failed to write {path}: {err}"))?;
+            println!("{summary}");
+        }
+        None => {
+            print!("{puzzle}");
+            eprintln!("{summary}");
         }
-        Some('.') => true,
-        _ => false,
     }
+    Ok(())
 }
 
-fn move_vertical(grid: &mut Grid<char>, at: Vector, dy: i32) {
-    let value = grid.get(at);
-    match value {
-        Some('[' | ']') => {
-            let part = value.unwrap();
-            let (other, sign) = if part == '[' { (']', 1) } else { ('[', -1) };
-            move_vertical(grid, (at.0, at.1 + dy), dy);
-            move_vertical(grid, (at.0 + sign, at.1 + dy), dy);
-            grid.set((at.0, at.1), '.');
-            grid.set((at.0 + sign, at.1), '.');
-
-            grid.set((at.0, at.1 + dy), part);
-            grid.set((at.0 + sign, at.1 + dy), other);
-        }
-        _ => {}
+fn parse_widen(options: &HashMap<&str, &str>) -> Result<i32, String> {
+    match options.get("--widen") {
+        Some(value) => value.parse::<i32>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or(format!("Invalid --widen value: {value}")),
+        None if options.contains_key("--wide") => Ok(2),
+        None => Ok(1),
     }
 }
 
+fn parse_position(value: &str) -> Result<Vector, String> {
+    let (x, y) = value.split_once(',').ok_or(format!("Invalid position: {value}"))?;
+    let x = x.trim().parse::<i32>().map_err(|_| format!("Invalid position: {value}"))?;
+    let y = y.trim().parse::<i32>().map_err(|_| format!("Invalid position: {value}"))?;
+    Ok((x, y))
+}
+
 type Vector = (i32, i32);
 
-fn parse_input(input: &str) -> (Grid<char>, String) {
+fn parse_input(input: &str, require_robot: bool) -> Result<(Grid<char>, String), String> {
+    let issues = validate::validate(input, require_robot);
+    if !issues.is_empty() {
+        return Err(validate::format_report(&issues));
+    }
+
     let mut scan_grid = true;
     let mut grid_lines: Vec<String> = vec![];
     let mut steps = String::new();
@@ -232,13 +303,13 @@ fn parse_input(input: &str) -> (Grid<char>, String) {
                 grid_lines.push(line.to_string());
             }
         } else {
-            steps += line;
+            steps.extend(line.chars().filter(|step| !step.is_whitespace()));
         }
     }
-    return (
-        Grid::from_lines(&grid_lines[..]).unwrap(),
+    return Ok((
+        Grid::from_lines(&grid_lines[..])?,
         steps
-    );
+    ));
 }
 
 fn direction_from_step(direction: char) -> Vector {
@@ -250,19 +321,3 @@ fn direction_from_step(direction: char) -> Vector {
         _ => (0, 0),
     }
 }
-
-fn compute_gps_sum(grid: &Grid<char>) -> i32 {
-    let mut total = 0;
-    for i in 0..grid.width() {
-        for j in 0..grid.height() {
-            match grid.get((i, j)) {
-                Some('O' | '[') => {
-                    let gps = j * 100 + i;
-                    total += gps;
-                }
-                _ => {}
-            }
-        }
-    }
-    return total;
-}
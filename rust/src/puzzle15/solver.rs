@@ -0,0 +1,399 @@
+use core::{AStar, AStarGraph, AStarNode, Grid};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::Vector;
+use super::warehouse::{BoxPlacement, Warehouse, find_pushed};
+
+const MOVES: [(char, Vector); 4] = [('^', (0, -1)), ('v', (0, 1)), ('<', (-1, 0)), ('>', (1, 0))];
+
+pub enum SolveTarget {
+    Layout(Vec<BoxPlacement>),
+    GpsSum(i32),
+}
+
+impl SolveTarget {
+    pub fn is_satisfied(&self, warehouse: &Warehouse) -> bool {
+        match self {
+            SolveTarget::Layout(layout) => {
+                let mut expected = layout.clone();
+                let mut actual = warehouse.placements();
+                expected.sort();
+                actual.sort();
+                expected == actual
+            }
+            SolveTarget::GpsSum(total) => warehouse.gps_sum() == *total,
+        }
+    }
+}
+
+pub fn solve(warehouse: &Warehouse, target: &SolveTarget, max_nodes: usize) -> Result<Option<String>, String> {
+    let graph = SokobanGraph::new(warehouse, target)?;
+    let mut astar = AStar::new(graph);
+    let mut expanded = 0;
+    while !astar.next() {
+        expanded += 1;
+        if expanded > max_nodes {
+            return Err(format!("Search exceeded the limit of {max_nodes} expanded states"));
+        }
+    }
+    let Some((goal, _)) = astar.found_goal() else {
+        return Ok(None);
+    };
+    let mut moves: Vec<char> = astar.iter_back_path(goal.key())
+        .filter_map(|(_, edge)| edge.copied())
+        .collect();
+    moves.reverse();
+    Ok(Some(moves.into_iter().collect()))
+}
+
+#[derive(Clone)]
+struct SokobanNode {
+    robot: Vector,
+    objects: Vec<(usize, Vector)>,
+}
+
+struct SokobanGraph<'a> {
+    walls: &'a Grid<bool>,
+    shapes: Vec<Vec<Vector>>,
+    start: SokobanNode,
+    goal: Goal,
+}
+
+enum Goal {
+    Layout {
+        sorted: Vec<(usize, Vector)>,
+        targets: Vec<Vec<Vector>>,
+        alive: Vec<HashSet<Vector>>,
+    },
+    GpsSum(i32),
+}
+
+impl AStarNode for SokobanNode {
+    type Key = (Vector, Vec<(usize, Vector)>);
+
+    fn key(&self) -> Self::Key {
+        // Boxes of the same shape are interchangeable, so the key ignores their identities
+        let mut objects = self.objects.clone();
+        objects.sort();
+        (self.robot, objects)
+    }
+}
+
+impl<'a> SokobanGraph<'a> {
+    fn new(warehouse: &'a Warehouse, target: &SolveTarget) -> Result<SokobanGraph<'a>, String> {
+        let mut shape_ids: HashMap<Vec<(Vector, char)>, usize> = HashMap::new();
+        let mut shapes: Vec<Vec<Vector>> = Vec::new();
+        let mut objects = Vec::new();
+        for placement in warehouse.placements() {
+            let next_id = shape_ids.len();
+            let id = *shape_ids.entry(placement.shape.clone()).or_insert_with(|| {
+                shapes.push(placement.shape.iter().map(|&(cell, _)| cell).collect());
+                next_id
+            });
+            objects.push((id, placement.top_left));
+        }
+
+        let goal = match target {
+            SolveTarget::GpsSum(total) => Goal::GpsSum(*total),
+            SolveTarget::Layout(layout) => {
+                let mut targets: Vec<Vec<Vector>> = vec![Vec::new(); shapes.len()];
+                for placement in layout {
+                    let id = shape_ids.get(&placement.shape)
+                        .ok_or(format!("Target box at {:?} has a shape not present in the warehouse", placement.top_left))?;
+                    targets[*id].push(placement.top_left);
+                }
+                for (id, shape_targets) in targets.iter().enumerate() {
+                    let count = objects.iter().filter(|&&(shape, _)| shape == id).count();
+                    if count != shape_targets.len() {
+                        return Err(format!(
+                            "Target layout has {} boxes of a shape that appears {} times in the warehouse",
+                            shape_targets.len(), count
+                        ));
+                    }
+                }
+                let mut sorted: Vec<(usize, Vector)> = targets.iter()
+                    .enumerate()
+                    .flat_map(|(id, positions)| positions.iter().map(move |&position| (id, position)))
+                    .collect();
+                sorted.sort();
+                let alive = targets.iter()
+                    .enumerate()
+                    .map(|(id, shape_targets)| Self::alive_positions(warehouse.walls(), &shapes[id], shape_targets))
+                    .collect();
+                Goal::Layout { sorted, targets, alive }
+            }
+        };
+
+        Ok(SokobanGraph {
+            walls: warehouse.walls(),
+            shapes,
+            start: SokobanNode { robot: warehouse.robot(), objects },
+            goal,
+        })
+    }
+
+    fn is_wall(&self, cell: Vector) -> bool {
+        self.walls.get(cell).unwrap_or(true)
+    }
+
+    fn cells_at(&self, shape: usize, (x, y): Vector) -> Vec<Vector> {
+        self.shapes[shape].iter().map(|&(dx, dy)| (x + dx, y + dy)).collect()
+    }
+
+    fn has_pusher(&self, cells: &[Vector], (dx, dy): Vector) -> bool {
+        cells.iter()
+            .map(|&(x, y)| (x - dx, y - dy))
+            .any(|cell| !cells.contains(&cell) && !self.is_wall(cell))
+    }
+
+    // Positions from which a lone box of the shape can still be pushed onto one of the targets
+    fn alive_positions(walls: &Grid<bool>, shape: &[Vector], targets: &[Vector]) -> HashSet<Vector> {
+        let is_wall = |cell: Vector| walls.get(cell).unwrap_or(true);
+        let cells_at = |(x, y): Vector| shape.iter().map(|&(dx, dy)| (x + dx, y + dy)).collect::<Vec<_>>();
+        let fits = |at: Vector| cells_at(at).into_iter().all(|cell| !is_wall(cell));
+
+        let mut alive: HashSet<Vector> = targets.iter().copied().filter(|&at| fits(at)).collect();
+        let mut queue: VecDeque<Vector> = alive.iter().copied().collect();
+        while let Some((x, y)) = queue.pop_front() {
+            for (_, (dx, dy)) in MOVES {
+                let from = (x - dx, y - dy);
+                if alive.contains(&from) || !fits(from) {
+                    continue;
+                }
+                let cells = cells_at(from);
+                let pushable = cells.iter()
+                    .map(|&(cx, cy)| (cx - dx, cy - dy))
+                    .any(|cell| !cells.contains(&cell) && !is_wall(cell));
+                if pushable {
+                    alive.insert(from);
+                    queue.push_back(from);
+                }
+            }
+        }
+        alive
+    }
+
+    fn occupancy(&self, node: &SokobanNode) -> HashMap<Vector, usize> {
+        let mut occupancy = HashMap::new();
+        for (id, &(shape, position)) in node.objects.iter().enumerate() {
+            for cell in self.cells_at(shape, position) {
+                occupancy.insert(cell, id);
+            }
+        }
+        occupancy
+    }
+
+    fn is_frozen(&self, id: usize, node: &SokobanNode, occupancy: &HashMap<Vector, usize>, visiting: &mut Vec<usize>) -> bool {
+        visiting.push(id);
+        let frozen = [[(0, -1), (0, 1)], [(-1, 0), (1, 0)]].iter()
+            .all(|axis| axis.iter().all(|&direction| !self.can_move(id, direction, node, occupancy, visiting)));
+        visiting.pop();
+        frozen
+    }
+
+    fn can_move(
+        &self,
+        id: usize,
+        (dx, dy): Vector,
+        node: &SokobanNode,
+        occupancy: &HashMap<Vector, usize>,
+        visiting: &mut Vec<usize>,
+    ) -> bool {
+        let (shape, position) = node.objects[id];
+        let cells = self.cells_at(shape, position);
+        if !self.has_pusher(&cells, (dx, dy)) {
+            return false;
+        }
+        for &(x, y) in cells.iter() {
+            let next = (x + dx, y + dy);
+            if cells.contains(&next) {
+                continue;
+            }
+            if self.is_wall(next) {
+                return false;
+            }
+            if let Some(&other) = occupancy.get(&next) {
+                if visiting.contains(&other) || self.is_frozen(other, node, occupancy, visiting) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn is_deadlocked(&self, node: &SokobanNode, occupancy: &HashMap<Vector, usize>, moved: &[usize]) -> bool {
+        let Goal::Layout { targets, alive, .. } = &self.goal else {
+            return false;
+        };
+        moved.iter().any(|&id| {
+            let (shape, position) = node.objects[id];
+            if !alive[shape].contains(&position) {
+                return true;
+            }
+            !targets[shape].contains(&position) && self.is_frozen(id, node, occupancy, &mut Vec::new())
+        })
+    }
+
+}
+
+impl<'a> AStarGraph<SokobanNode> for SokobanGraph<'a> {
+    type Edge = char;
+    type Cost = u32;
+
+    fn start(&self) -> SokobanNode {
+        self.start.clone()
+    }
+
+    fn neighbors(&self, node: &SokobanNode) -> impl Iterator<Item = (SokobanNode, Self::Edge, Self::Cost)> + '_ {
+        let occupancy = self.occupancy(node);
+        let mut neighbors = Vec::new();
+        for (step, direction) in MOVES {
+            let pushed = find_pushed(
+                node.robot,
+                direction,
+                |cell| self.is_wall(cell),
+                |cell| occupancy.get(&cell).copied(),
+                |id| self.cells_at(node.objects[id].0, node.objects[id].1),
+            );
+            let Some(pushed) = pushed else {
+                continue;
+            };
+            let mut next = node.clone();
+            next.robot = (node.robot.0 + direction.0, node.robot.1 + direction.1);
+            for &id in pushed.iter() {
+                let (x, y) = next.objects[id].1;
+                next.objects[id].1 = (x + direction.0, y + direction.1);
+            }
+            if !pushed.is_empty() && self.is_deadlocked(&next, &self.occupancy(&next), &pushed) {
+                continue;
+            }
+            neighbors.push((next, step, 1));
+        }
+        neighbors.into_iter()
+    }
+
+    fn estimate(&self, node: &SokobanNode) -> Self::Cost {
+        let Goal::Layout { targets, .. } = &self.goal else {
+            return 0;
+        };
+        // A single move can push many boxes at once, so only the farthest box gives an admissible bound
+        node.objects.iter()
+            .map(|&(shape, (x, y))| {
+                targets[shape].iter()
+                    .map(|&(tx, ty)| (x - tx).unsigned_abs() + (y - ty).unsigned_abs())
+                    .min()
+                    .unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_goal(&self, node: &SokobanNode) -> bool {
+        match &self.goal {
+            Goal::Layout { sorted, .. } => node.key().1 == *sorted,
+            Goal::GpsSum(total) => {
+                node.objects.iter().map(|&(_, (x, y))| y * 100 + x).sum::<i32>() == *total
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_grid(text: &str) -> Grid<char> {
+        Grid::from_lines(&text.lines().map(String::from).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn layout_target(text: &str) -> SolveTarget {
+        SolveTarget::Layout(Warehouse::box_layout(&parse_grid(text)).unwrap())
+    }
+
+    fn replay(warehouse: &mut Warehouse, moves: &str) {
+        for step in moves.chars() {
+            warehouse.apply(step);
+        }
+    }
+
+    const CORRIDOR: &str = "\
+#######
+#.....#
+#.@O..#
+#.....#
+#######";
+
+    #[test]
+    fn solves_small_targets() {
+        let mut warehouse = Warehouse::from_grid(&parse_grid(CORRIDOR)).unwrap();
+        let target = layout_target("\
+#######
+#.....#
+#....O#
+#.....#
+#######");
+        assert_eq!(solve(&warehouse, &target, 10_000), Ok(Some(">>".to_string())));
+        assert_eq!(solve(&warehouse, &SolveTarget::GpsSum(204), 10_000), Ok(Some(">".to_string())));
+
+        replay(&mut warehouse, ">>");
+        assert!(target.is_satisfied(&warehouse));
+    }
+
+    #[test]
+    fn reports_unreachable_target() {
+        let warehouse = Warehouse::from_grid(&parse_grid("\
+#######
+#..O..#
+#.@...#
+#.....#
+#######")).unwrap();
+        // Nothing can pull the box away from the top wall
+        let target = layout_target(CORRIDOR);
+        assert_eq!(solve(&warehouse, &target, 10_000), Ok(None));
+    }
+
+    #[test]
+    fn rejects_target_shapes_missing_from_the_warehouse() {
+        let warehouse = Warehouse::from_grid(&parse_grid(CORRIDOR)).unwrap();
+        let target = layout_target("\
+#######
+#.....#
+#..[].#
+#.....#
+#######");
+        assert!(solve(&warehouse, &target, 10_000).is_err());
+    }
+
+    #[test]
+    fn prunes_pushes_into_deadlocks() {
+        let mut warehouse = Warehouse::from_grid(&parse_grid("\
+######
+#....#
+#.O@.#
+#....#
+######")).unwrap();
+        let target = layout_target("\
+######
+#.O..#
+#....#
+#....#
+######");
+        let graph = SokobanGraph::new(&warehouse, &target).unwrap();
+        let Goal::Layout { alive, .. } = &graph.goal else {
+            panic!("Expected a layout goal");
+        };
+        assert!(alive[0].contains(&(2, 2)));
+        assert!(!alive[0].contains(&(1, 2)));
+        assert!(!alive[0].contains(&(4, 3)));
+
+        // Pushing the box against the left wall would strand it there
+        let mut steps: Vec<char> = graph.neighbors(&graph.start()).map(|(_, step, _)| step).collect();
+        steps.sort();
+        assert_eq!(steps, ['>', '^', 'v']);
+
+        let moves = solve(&warehouse, &target, 10_000).unwrap().unwrap();
+        assert_eq!(moves.len(), 3);
+        replay(&mut warehouse, &moves);
+        assert!(target.is_satisfied(&warehouse));
+    }
+}
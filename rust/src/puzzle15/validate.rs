@@ -0,0 +1,148 @@
+use std::fmt;
+
+pub struct Issue {
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "line {line}, column {column}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+pub fn format_report(issues: &[Issue]) -> String {
+    let mut report = format!("Found {} problem(s) in the warehouse file:", issues.len());
+    for issue in issues {
+        report += &format!("\n  {issue}");
+    }
+    report
+}
+
+pub fn validate(input: &str, require_robot: bool) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let lines: Vec<&str> = input.lines().collect();
+    let grid_end = lines.iter().position(|line| line.is_empty()).unwrap_or(lines.len());
+    let rows: Vec<Vec<char>> = lines[..grid_end].iter().map(|line| line.chars().collect()).collect();
+
+    let mut issue = |line: usize, column: usize, message: String| {
+        issues.push(Issue { position: Some((line + 1, column + 1)), message });
+    };
+
+    let Some(width) = rows.first().map(|row| row.len()) else {
+        return vec![Issue { position: None, message: "Warehouse map is empty".to_string() }];
+    };
+    let mut robots = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        if row.len() != width {
+            issue(y, row.len().min(width), format!("Row has {} cells, expected {width}", row.len()));
+        }
+        let mut open_box = None;
+        for (x, &cell) in row.iter().enumerate() {
+            let on_border = y == 0 || y == rows.len() - 1 || x == 0 || x == row.len() - 1;
+            if on_border && cell != '#' {
+                issue(y, x, format!("Warehouse border is not enclosed, found '{cell}' instead of '#'"));
+            }
+            match cell {
+                '[' | '=' | ']' => {}
+                _ => {
+                    if let Some(start) = open_box.take() {
+                        issue(y, start, "Wide box '[' is not closed by ']'".to_string());
+                    }
+                }
+            }
+            match cell {
+                '#' | '.' | 'O' | 'a'..='z' => {}
+                '@' => robots.push((y, x)),
+                '[' => {
+                    if let Some(start) = open_box.replace(x) {
+                        issue(y, start, "Wide box '[' is not closed by ']'".to_string());
+                    }
+                }
+                '=' | ']' => {
+                    if open_box.is_none() {
+                        issue(y, x, format!("Wide box part '{cell}' has no opening '['"));
+                    } else if cell == ']' {
+                        open_box = None;
+                    }
+                }
+                other => issue(y, x, format!("Unknown warehouse cell '{other}'")),
+            }
+        }
+        if let Some(start) = open_box {
+            issue(y, start, "Wide box '[' is not closed by ']'".to_string());
+        }
+    }
+    for &(y, x) in robots.iter().skip(1) {
+        issue(y, x, format!("Extra robot, the first one is at line {}, column {}", robots[0].0 + 1, robots[0].1 + 1));
+    }
+
+    for (y, line) in lines.iter().enumerate().skip(grid_end + 1) {
+        for (x, step) in line.chars().enumerate() {
+            if !matches!(step, '^' | 'v' | '<' | '>') && !step.is_whitespace() {
+                issue(y, x, format!("Unknown move character '{step}'"));
+            }
+        }
+    }
+
+    if require_robot && robots.is_empty() {
+        issues.push(Issue { position: None, message: "Warehouse has no robot '@'".to_string() });
+    }
+    issues.sort_by_key(|issue| (issue.position.is_none(), issue.position));
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(input: &str, require_robot: bool) -> Vec<String> {
+        validate(input, require_robot).iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn accepts_well_formed_warehouse() {
+        let input = "#######\n#.@O..#\n#.[]a.#\n#..aa.#\n#######\n\n<^^>\nvv<\n";
+        assert!(validate(input, true).is_empty());
+    }
+
+    #[test]
+    fn reports_every_problem_with_its_position() {
+        let input = "#####\n#.@%#\n#[..#\n#@..\n####.\n\n<>?\n";
+        assert_eq!(report(input, true), [
+            "line 2, column 4: Unknown warehouse cell '%'",
+            "line 3, column 2: Wide box '[' is not closed by ']'",
+            "line 4, column 2: Extra robot, the first one is at line 2, column 3",
+            "line 4, column 4: Warehouse border is not enclosed, found '.' instead of '#'",
+            "line 4, column 5: Row has 4 cells, expected 5",
+            "line 5, column 5: Warehouse border is not enclosed, found '.' instead of '#'",
+            "line 7, column 3: Unknown move character '?'",
+        ]);
+    }
+
+    #[test]
+    fn reports_unbalanced_wide_boxes() {
+        let input = "########\n#.=].[[#\n#@.....#\n########\n";
+        assert_eq!(report(input, true), [
+            "line 2, column 3: Wide box part '=' has no opening '['",
+            "line 2, column 4: Wide box part ']' has no opening '['",
+            "line 2, column 6: Wide box '[' is not closed by ']'",
+            "line 2, column 7: Wide box '[' is not closed by ']'",
+        ]);
+    }
+
+    #[test]
+    fn reports_missing_robot_and_empty_map() {
+        let input = "###\n#.#\n###\n";
+        assert_eq!(report(input, true), ["Warehouse has no robot '@'"]);
+        assert!(validate(input, false).is_empty());
+        assert_eq!(report("", true), ["Warehouse map is empty"]);
+        assert_eq!(
+            format_report(&validate(input, true)),
+            "Found 1 problem(s) in the warehouse file:\n  Warehouse has no robot '@'"
+        );
+    }
+}
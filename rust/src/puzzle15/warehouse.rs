@@ -0,0 +1,521 @@
+use core::Grid;
+use std::collections::{HashSet, VecDeque};
+
+use super::{Vector, direction_from_step};
+
+pub const PALETTE: [[u8; 3]; 5] = [
+    [24, 24, 32],
+    [110, 110, 120],
+    [220, 60, 50],
+    [150, 100, 50],
+    [240, 200, 60],
+];
+const COLOR_EMPTY: u8 = 0;
+const COLOR_WALL: u8 = 1;
+const COLOR_ROBOT: u8 = 2;
+const COLOR_BOX: u8 = 3;
+const COLOR_MOVED: u8 = 4;
+
+pub struct StepDelta {
+    pub step: char,
+    pub direction: Vector,
+    pub robot_moved: bool,
+    pub moved: Vec<usize>,
+}
+
+struct BoxObject {
+    cells: Vec<Vector>,
+    glyphs: Vec<char>,
+}
+
+pub struct Warehouse {
+    walls: Grid<bool>,
+    robot: Vector,
+    objects: Vec<BoxObject>,
+    occupancy: Grid<Option<usize>>,
+    history: Vec<StepDelta>,
+    applied: usize,
+}
+
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BoxPlacement {
+    pub shape: Vec<(Vector, char)>,
+    pub top_left: Vector,
+}
+
+type ParsedLayout = (Grid<bool>, Option<Vector>, Vec<BoxObject>, Grid<Option<usize>>);
+
+impl Warehouse {
+    pub fn from_grid(grid: &Grid<char>) -> Result<Warehouse, String> {
+        let (walls, robot, objects, occupancy) = Self::parse_layout(grid)?;
+        let robot = robot.ok_or("No robot found in the warehouse")?;
+        Ok(Warehouse {
+            walls,
+            robot,
+            objects,
+            occupancy,
+            history: Vec::new(),
+            applied: 0,
+        })
+    }
+
+    pub fn box_layout(grid: &Grid<char>) -> Result<Vec<BoxPlacement>, String> {
+        let (_, _, objects, _) = Self::parse_layout(grid)?;
+        Ok(objects.iter().map(|object| object.placement()).collect())
+    }
+
+    fn parse_layout(grid: &Grid<char>) -> Result<ParsedLayout, String> {
+        let mut walls = Grid::new(grid.width(), grid.height(), false);
+        let mut occupancy: Grid<Option<usize>> = Grid::new(grid.width(), grid.height(), None);
+        let mut objects: Vec<BoxObject> = Vec::new();
+        let mut robot = None;
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if occupancy.get((x, y)).flatten().is_some() {
+                    continue;
+                }
+                let cells = match grid.get((x, y)).unwrap() {
+                    '#' => {
+                        walls.set((x, y), true);
+                        continue;
+                    }
+                    '@' => {
+                        if robot.replace((x, y)).is_some() {
+                            return Err(format!("Multiple robots found, second one at ({x}, {y})"));
+                        }
+                        continue;
+                    }
+                    '.' => {
+                        continue;
+                    }
+                    'O' => vec![((x, y), 'O')],
+                    '[' => Self::scan_wide_box(grid, (x, y))?,
+                    letter @ 'a'..='z' => Self::flood_letter_box(grid, (x, y), letter),
+                    other @ ('=' | ']') => {
+                        return Err(format!("Unmatched wide box part '{other}' at ({x}, {y})"));
+                    }
+                    other => {
+                        return Err(format!("Unexpected warehouse cell '{other}' at ({x}, {y})"));
+                    }
+                };
+                for &(cell, _) in cells.iter() {
+                    occupancy.set(cell, Some(objects.len()));
+                }
+                objects.push(BoxObject {
+                    cells: cells.iter().map(|&(cell, _)| cell).collect(),
+                    glyphs: cells.iter().map(|&(_, glyph)| glyph).collect(),
+                });
+            }
+        }
+        Ok((walls, robot, objects, occupancy))
+    }
+
+    fn scan_wide_box(grid: &Grid<char>, (x, y): Vector) -> Result<Vec<(Vector, char)>, String> {
+        let mut cells = vec![((x, y), '[')];
+        for end in x + 1.. {
+            match grid.get((end, y)) {
+                Some('=') => cells.push(((end, y), '=')),
+                Some(']') => {
+                    cells.push(((end, y), ']'));
+                    return Ok(cells);
+                }
+                _ => break,
+            }
+        }
+        Err(format!("Unterminated wide box starting at ({x}, {y})"))
+    }
+
+    fn flood_letter_box(grid: &Grid<char>, start: Vector, letter: char) -> Vec<(Vector, char)> {
+        let mut cells = Vec::new();
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some((x, y)) = queue.pop_front() {
+            cells.push(((x, y), letter));
+            for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if grid.get(next) == Some(letter) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        cells
+    }
+
+    pub fn walls(&self) -> &Grid<bool> {
+        &self.walls
+    }
+
+    pub fn robot(&self) -> Vector {
+        self.robot
+    }
+
+    pub fn placements(&self) -> Vec<BoxPlacement> {
+        self.objects.iter().map(|object| object.placement()).collect()
+    }
+
+    pub fn history(&self) -> &[StepDelta] {
+        &self.history[..self.applied]
+    }
+
+    pub fn box_at(&self, at: Vector) -> Option<usize> {
+        self.occupancy.get(at).flatten()
+    }
+
+    pub fn apply(&mut self, step: char) -> &StepDelta {
+        let direction = direction_from_step(step);
+        let pushed = if direction == (0, 0) { None } else { self.find_pushed(direction) };
+        let delta = StepDelta {
+            step,
+            direction,
+            robot_moved: pushed.is_some(),
+            moved: pushed.unwrap_or_default(),
+        };
+        self.history.truncate(self.applied);
+        Self::execute(&mut self.objects, &mut self.occupancy, &mut self.robot, &delta, 1);
+        self.history.push(delta);
+        self.applied += 1;
+        &self.history[self.applied - 1]
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        if self.applied == 0 {
+            return false;
+        }
+        self.applied -= 1;
+        let delta = &self.history[self.applied];
+        Self::execute(&mut self.objects, &mut self.occupancy, &mut self.robot, delta, -1);
+        true
+    }
+
+    pub fn step_forward(&mut self) -> bool {
+        if self.applied >= self.history.len() {
+            return false;
+        }
+        let delta = &self.history[self.applied];
+        Self::execute(&mut self.objects, &mut self.occupancy, &mut self.robot, delta, 1);
+        self.applied += 1;
+        true
+    }
+
+    pub fn seek(&mut self, step: usize) {
+        while self.applied > step && self.step_back() {}
+        while self.applied < step && self.step_forward() {}
+    }
+
+    pub fn first_move(&self, box_id: usize) -> Option<usize> {
+        self.history().iter().position(|delta| delta.moved.contains(&box_id))
+    }
+
+    fn find_pushed(&self, direction: Vector) -> Option<Vec<usize>> {
+        find_pushed(
+            self.robot,
+            direction,
+            |cell| self.walls.get(cell).unwrap_or(true),
+            |cell| self.box_at(cell),
+            |id| self.objects[id].cells.clone(),
+        )
+    }
+
+    fn execute(
+        objects: &mut [BoxObject],
+        occupancy: &mut Grid<Option<usize>>,
+        robot: &mut Vector,
+        delta: &StepDelta,
+        sign: i32,
+    ) {
+        let (dx, dy) = (delta.direction.0 * sign, delta.direction.1 * sign);
+        for &id in delta.moved.iter() {
+            for &cell in objects[id].cells.iter() {
+                occupancy.set(cell, None);
+            }
+        }
+        for &id in delta.moved.iter() {
+            for cell in objects[id].cells.iter_mut() {
+                *cell = (cell.0 + dx, cell.1 + dy);
+                occupancy.set(*cell, Some(id));
+            }
+        }
+        if delta.robot_moved {
+            *robot = (robot.0 + dx, robot.1 + dy);
+        }
+    }
+
+    pub fn to_grid(&self) -> Grid<char> {
+        let mut grid = self.walls.map(|&wall| if wall { '#' } else { '.' });
+        for object in self.objects.iter() {
+            for (&cell, &glyph) in object.cells.iter().zip(object.glyphs.iter()) {
+                grid.set(cell, glyph);
+            }
+        }
+        grid.set(self.robot, '@');
+        grid
+    }
+
+    pub fn gps_sum(&self) -> i32 {
+        self.objects.iter()
+            .map(|object| {
+                let (x, y) = object.top_left();
+                y * 100 + x
+            })
+            .sum()
+    }
+
+    pub fn render_pixels(&self, scale: i32, highlight: &[usize]) -> (u16, u16, Vec<u8>) {
+        let width = self.walls.width() * scale;
+        let height = self.walls.height() * scale;
+        let mut pixels = vec![COLOR_EMPTY; (width * height) as usize];
+        let mut fill = |(x, y): Vector, color: u8| {
+            for py in y * scale..(y + 1) * scale {
+                for px in x * scale..(x + 1) * scale {
+                    pixels[(py * width + px) as usize] = color;
+                }
+            }
+        };
+        for y in 0..self.walls.height() {
+            for x in 0..self.walls.width() {
+                if self.walls.get((x, y)).unwrap() {
+                    fill((x, y), COLOR_WALL);
+                }
+            }
+        }
+        for (id, object) in self.objects.iter().enumerate() {
+            let color = if highlight.contains(&id) { COLOR_MOVED } else { COLOR_BOX };
+            for &cell in object.cells.iter() {
+                fill(cell, color);
+            }
+        }
+        fill(self.robot, COLOR_ROBOT);
+        (width as u16, height as u16, pixels)
+    }
+}
+
+impl BoxObject {
+    // The topmost occupied cell, leftmost on ties, so that it is part of the box even for L shapes
+    fn top_left(&self) -> Vector {
+        *self.cells.iter().min_by_key(|&&(x, y)| (y, x)).unwrap()
+    }
+
+    fn placement(&self) -> BoxPlacement {
+        let top_left = self.top_left();
+        let mut shape: Vec<(Vector, char)> = self.cells.iter()
+            .zip(self.glyphs.iter())
+            .map(|(&(x, y), &glyph)| ((x - top_left.0, y - top_left.1), glyph))
+            .collect();
+        shape.sort();
+        BoxPlacement { shape, top_left }
+    }
+}
+
+pub fn find_pushed(
+    robot: Vector,
+    direction: Vector,
+    is_wall: impl Fn(Vector) -> bool,
+    object_at: impl Fn(Vector) -> Option<usize>,
+    cells_of: impl Fn(usize) -> Vec<Vector>,
+) -> Option<Vec<usize>> {
+    let mut pushed = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([(robot.0 + direction.0, robot.1 + direction.1)]);
+    while let Some(cell) = queue.pop_front() {
+        if is_wall(cell) {
+            return None;
+        }
+        if let Some(id) = object_at(cell) {
+            if seen.insert(id) {
+                pushed.push(id);
+                queue.extend(cells_of(id).into_iter().map(|(x, y)| (x + direction.0, y + direction.1)));
+            }
+        }
+    }
+    Some(pushed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL_SAMPLE: &str = "\
+########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########";
+    const SMALL_STEPS: &str = "<^^>>>vv<v>>v<<";
+
+    fn parse_grid(text: &str) -> Grid<char> {
+        Grid::from_lines(&text.lines().map(String::from).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn render(warehouse: &Warehouse) -> String {
+        warehouse.to_grid().lines().collect::<String>().trim_end().to_string()
+    }
+
+    fn small_warehouse() -> Warehouse {
+        Warehouse::from_grid(&parse_grid(SMALL_SAMPLE)).unwrap()
+    }
+
+    #[test]
+    fn applies_and_reverts_steps() {
+        let mut warehouse = small_warehouse();
+        for step in SMALL_STEPS.chars() {
+            warehouse.apply(step);
+        }
+        let finished = "\
+########
+#....OO#
+##.....#
+#.....O#
+#.#O@..#
+#...O..#
+#...O..#
+########";
+        assert_eq!(render(&warehouse), finished);
+        assert_eq!(warehouse.gps_sum(), 2028);
+        assert_eq!(warehouse.history().len(), SMALL_STEPS.len());
+
+        while warehouse.step_back() {}
+        assert_eq!(render(&warehouse), SMALL_SAMPLE);
+        assert!(warehouse.history().is_empty());
+
+        warehouse.seek(SMALL_STEPS.len());
+        assert_eq!(render(&warehouse), finished);
+    }
+
+    #[test]
+    fn records_step_deltas() {
+        let mut warehouse = small_warehouse();
+        let deltas: Vec<(bool, Vec<usize>)> = SMALL_STEPS.chars()
+            .take(7)
+            .map(|step| {
+                let delta = warehouse.apply(step);
+                (delta.robot_moved, delta.moved.clone())
+            })
+            .collect();
+        assert_eq!(deltas, [
+            (false, vec![]),
+            (true, vec![]),
+            (false, vec![]),
+            (true, vec![0]),
+            (true, vec![0, 1]),
+            (false, vec![]),
+            (true, vec![2, 3, 4, 5]),
+        ]);
+        assert_eq!(warehouse.first_move(0), Some(3));
+        assert_eq!(warehouse.first_move(1), Some(4));
+        assert_eq!(warehouse.first_move(2), Some(6));
+    }
+
+    #[test]
+    fn seeks_through_history() {
+        let mut warehouse = small_warehouse();
+        for step in SMALL_STEPS.chars() {
+            warehouse.apply(step);
+        }
+        warehouse.seek(5);
+        assert_eq!(warehouse.box_at((5, 1)), Some(0));
+        assert_eq!(warehouse.box_at((6, 1)), Some(1));
+        assert_eq!(warehouse.box_at((3, 1)), None);
+        assert_eq!(warehouse.first_move(2), None);
+
+        warehouse.seek(3);
+        assert_eq!(warehouse.box_at((3, 1)), Some(0));
+        assert_eq!(warehouse.robot(), (2, 1));
+
+        // Applying a step after seeking back drops the steps that followed
+        warehouse.apply('v');
+        assert_eq!(warehouse.history().len(), 4);
+        assert!(!warehouse.step_forward());
+        assert_eq!(warehouse.robot(), (2, 2));
+    }
+
+    #[test]
+    fn renders_pixels() {
+        let mut warehouse = small_warehouse();
+        let pixel = |pixels: &[u8], (x, y): Vector| pixels[(y * 16 + x) as usize];
+
+        let (width, height, pixels) = warehouse.render_pixels(2, &[]);
+        assert_eq!((width, height, pixels.len()), (16, 16, 256));
+        assert_eq!(pixel(&pixels, (0, 0)), COLOR_WALL);
+        assert_eq!(pixel(&pixels, (2, 2)), COLOR_EMPTY);
+        assert_eq!(pixel(&pixels, (5, 5)), COLOR_ROBOT);
+        assert_eq!(pixel(&pixels, (6, 2)), COLOR_BOX);
+
+        for step in "<^^".chars() {
+            warehouse.apply(step);
+        }
+        let moved = warehouse.apply('>').moved.clone();
+        let (_, _, pixels) = warehouse.render_pixels(2, &moved);
+        assert_eq!(moved, [0]);
+        assert_eq!(pixel(&pixels, (6, 2)), COLOR_ROBOT);
+        assert_eq!(pixel(&pixels, (8, 2)), COLOR_MOVED);
+        assert_eq!(pixel(&pixels, (10, 2)), COLOR_BOX);
+    }
+
+    #[test]
+    fn pushes_stacked_wide_boxes() {
+        let mut warehouse = Warehouse::from_grid(&parse_grid("\
+##########
+#........#
+#..[=]...#
+#....[]..#
+#....@...#
+##########")).unwrap();
+        assert_eq!(warehouse.find_pushed((0, -1)), Some(vec![1, 0]));
+        assert_eq!(warehouse.find_pushed((1, 0)), Some(vec![]));
+        warehouse.apply('^');
+        assert_eq!(render(&warehouse), "\
+##########
+#..[=]...#
+#....[]..#
+#....@...#
+#........#
+##########");
+        // The top box now rests against the wall
+        assert_eq!(warehouse.find_pushed((0, -1)), None);
+    }
+
+    #[test]
+    fn pushes_letter_polyomino() {
+        let mut warehouse = Warehouse::from_grid(&parse_grid("\
+########
+#......#
+#.a....#
+#.aaO..#
+#..@...#
+########")).unwrap();
+        // Pushing the corner of the L moves the whole letter box, and only the letter box
+        assert_eq!(warehouse.find_pushed((0, -1)), Some(vec![0]));
+        warehouse.apply('^');
+        assert_eq!(render(&warehouse), "\
+########
+#.a....#
+#.aa...#
+#..@O..#
+#......#
+########");
+        assert_eq!(warehouse.gps_sum(), 102 + 304);
+        assert_eq!(warehouse.find_pushed((1, 0)), Some(vec![1]));
+        // The upright of the L reaches the wall before the pushed corner does
+        assert_eq!(warehouse.find_pushed((0, -1)), None);
+    }
+
+    #[test]
+    fn blocks_push_when_any_cell_hits_a_wall() {
+        let mut warehouse = Warehouse::from_grid(&parse_grid("\
+#######
+#.O...#
+#.b...#
+#.bb..#
+#..@..#
+#######")).unwrap();
+        assert_eq!(warehouse.find_pushed((0, -1)), None);
+        let delta = warehouse.apply('^');
+        assert!(!delta.robot_moved);
+        assert!(delta.moved.is_empty());
+        assert_eq!(warehouse.robot(), (3, 4));
+        assert_eq!(warehouse.box_at((2, 1)), Some(0));
+        assert_eq!(warehouse.box_at((3, 3)), Some(1));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use super::Move;
+
+pub struct CostModel {
+    pub forward: i32,
+    pub turn_left: i32,
+    pub turn_right: i32,
+    pub turn_around: Option<i32>,
+    pub diagonal: Option<i32>,
+    pub jump: Option<(i32, i32)>,
+    terrain: HashMap<char, i32>,
+}
+
+impl Default for CostModel {
+    fn default() -> CostModel {
+        CostModel {
+            forward: 1,
+            turn_left: 1000,
+            turn_right: 1000,
+            turn_around: None,
+            diagonal: None,
+            jump: None,
+            terrain: HashMap::from([('.', 0), ('S', 0), ('E', 0)]),
+        }
+    }
+}
+
+impl CostModel {
+    pub fn from_options(options: &HashMap<&str, &str>) -> Result<CostModel, String> {
+        let mut model = CostModel::default();
+        let parse_cost = |name: &str| -> Result<Option<i32>, String> {
+            options.get(name)
+                .map(|value| value.parse::<i32>()
+                    .ok()
+                    .filter(|&cost| cost > 0)
+                    .ok_or(format!("Invalid {name} value: {value}, expected a positive cost")))
+                .transpose()
+        };
+        if let Some(cost) = parse_cost("--forward")? {
+            model.forward = cost;
+        }
+        if let Some(cost) = parse_cost("--turn")? {
+            model.turn_left = cost;
+            model.turn_right = cost;
+        }
+        if let Some(cost) = parse_cost("--turn-left")? {
+            model.turn_left = cost;
+        }
+        if let Some(cost) = parse_cost("--turn-right")? {
+            model.turn_right = cost;
+        }
+        model.turn_around = parse_cost("--turn-around")?;
+        model.diagonal = parse_cost("--diagonal")?;
+        if let Some(value) = options.get("--jump") {
+            let invalid = || format!("Invalid --jump value: {value}, expected <distance>:<cost>");
+            let (distance, cost) = value.split_once(':').ok_or_else(invalid)?;
+            let distance = distance.parse::<i32>().ok().filter(|&distance| distance > 1).ok_or_else(invalid)?;
+            let cost = cost.parse::<i32>().ok().filter(|&cost| cost > 0).ok_or_else(invalid)?;
+            model.jump = Some((distance, cost));
+        }
+        Ok(model)
+    }
+
+    pub fn load_legend(&mut self, content: &str) -> Result<(), String> {
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut chars = line.chars();
+            let (Some(tile), Some(' ')) = (chars.next(), chars.next()) else {
+                return Err(format!("Legend line {}: expected '<tile> <cost|wall>'", i + 1));
+            };
+            match chars.as_str().trim() {
+                "wall" => {
+                    self.terrain.remove(&tile);
+                }
+                cost => {
+                    let cost = cost.parse::<i32>()
+                        .ok()
+                        .filter(|&cost| cost >= 0)
+                        .ok_or(format!("Legend line {}: invalid cost '{cost}'", i + 1))?;
+                    self.terrain.insert(tile, cost);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn terrain_cost(&self, tile: char) -> Option<i32> {
+        self.terrain.get(&tile).copied()
+    }
+
+    pub fn move_cost(&self, step: Move) -> Option<i32> {
+        match step {
+            Move::Forward => Some(self.forward),
+            Move::TurnLeft => Some(self.turn_left),
+            Move::TurnRight => Some(self.turn_right),
+            Move::TurnAround => self.turn_around,
+            Move::DiagonalLeft | Move::DiagonalRight => self.diagonal,
+            Move::Jump => self.jump.map(|(_, cost)| cost),
+        }
+    }
+
+    // Lower bound of the remaining cost: every move covers a limited Manhattan distance for its price,
+    // and a goal that is not straight ahead needs at least one turn
+    pub fn estimate(&self, (dx, dy): (i32, i32), (fx, fy): (i32, i32)) -> i32 {
+        let distance = dx.abs() + dy.abs();
+        if distance == 0 {
+            return 0;
+        }
+        let min_terrain = self.terrain.values().copied().min().unwrap_or(0);
+        let mut rates = vec![(self.forward, 1)];
+        if let Some(cost) = self.diagonal {
+            rates.push((cost, 2));
+        }
+        if let Some((jump_distance, cost)) = self.jump {
+            rates.push((cost, jump_distance));
+        }
+        let travel = rates.into_iter()
+            .map(|(cost, covered)| distance * (cost + min_terrain) / covered)
+            .min()
+            .unwrap();
+
+        let ahead = dx * fx + dy * fy;
+        let lateral = (dx * fy - dy * fx).abs();
+        let needs_turn = ahead <= 0 || (lateral != 0 && (self.diagonal.is_none() || lateral > ahead));
+        let min_turn = [Some(self.turn_left), Some(self.turn_right), self.turn_around]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap();
+        travel + if needs_turn { min_turn } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MazeGraph;
+    use core::{AStar, Grid};
+
+    fn model(options: &[(&str, &str)]) -> Result<CostModel, String> {
+        CostModel::from_options(&options.iter().copied().collect())
+    }
+
+    fn path_cost(input: &str, costs: &CostModel) -> Option<i32> {
+        let maze = Grid::from_lines(&input.lines().map(String::from).collect::<Vec<String>>()).unwrap();
+        let mut astar = AStar::new(MazeGraph::new(&maze, costs));
+        while !astar.next() {}
+        astar.found_goal().as_ref().map(|(_, cost)| *cost)
+    }
+
+    #[test]
+    fn loads_terrain_legend() {
+        let mut costs = CostModel::default();
+        costs.load_legend("~ 5\n\n# 2\n. wall\n").unwrap();
+        assert_eq!(costs.terrain_cost('~'), Some(5));
+        assert_eq!(costs.terrain_cost('#'), Some(2));
+        assert_eq!(costs.terrain_cost('.'), None);
+        assert_eq!(costs.terrain_cost('S'), Some(0));
+        assert_eq!(path_cost("#####\n#S~E#\n#####", &costs), Some(2 + 5));
+
+        assert_eq!(costs.load_legend("~5"), Err("Legend line 1: expected '<tile> <cost|wall>'".to_string()));
+        assert_eq!(costs.load_legend("~ 1\n~ -1"), Err("Legend line 2: invalid cost '-1'".to_string()));
+    }
+
+    #[test]
+    fn prices_turning_around() {
+        let maze = "#####\n#E.S#\n#####";
+        assert_eq!(model(&[]).unwrap().move_cost(Move::TurnAround), None);
+        assert_eq!(path_cost(maze, &model(&[]).unwrap()), Some(2002));
+
+        let costs = model(&[("--turn-around", "500")]).unwrap();
+        assert_eq!(costs.move_cost(Move::TurnAround), Some(500));
+        assert_eq!(path_cost(maze, &costs), Some(502));
+
+        assert_eq!(model(&[("--turn-around", "0")]).err(),
+                   Some("Invalid --turn-around value: 0, expected a positive cost".to_string()));
+    }
+
+    #[test]
+    fn prices_diagonal_moves() {
+        let maze = "#####\n#..E#\n#S..#\n#####";
+        assert_eq!(path_cost(maze, &model(&[]).unwrap()), Some(1003));
+
+        let costs = model(&[("--diagonal", "3")]).unwrap();
+        assert_eq!(costs.move_cost(Move::DiagonalLeft), Some(3));
+        assert_eq!(costs.move_cost(Move::DiagonalRight), Some(3));
+        assert_eq!(path_cost(maze, &costs), Some(4));
+    }
+
+    #[test]
+    fn prices_jumps() {
+        let maze = "#####\n#S#E#\n#####";
+        assert_eq!(path_cost(maze, &model(&[]).unwrap()), None);
+
+        let costs = model(&[("--jump", "2:5")]).unwrap();
+        assert_eq!(costs.jump, Some((2, 5)));
+        assert_eq!(costs.move_cost(Move::Jump), Some(5));
+        assert_eq!(path_cost(maze, &costs), Some(5));
+
+        for value in ["1:5", "2", "2:0"] {
+            assert_eq!(model(&[("--jump", value)]).err(),
+                       Some(format!("Invalid --jump value: {value}, expected <distance>:<cost>")));
+        }
+    }
+}
@@ -1,16 +1,53 @@
-use core::{get_data_path, AStar, AStarGraph, AStarNode, Grid};
-use std::{fmt::Debug, fs::{read_to_string, File}, io::{LineWriter, Write}};
+use core::{get_data_path, AStar, AStarGraph, AStarNode, Grid, split_options};
+use cost_model::CostModel;
+use paths::OptimalPaths;
+use std::{env, fmt::Debug, fs::{read_to_string, File}, io::{LineWriter, Write}};
+use std::path::PathBuf;
+use std::process::exit;
+
+mod cost_model;
+mod paths;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (positional, options) = split_options(&args[1..], &[
+        "--routes", "--csv", "--legend",
+        "--forward", "--turn", "--turn-left", "--turn-right", "--turn-around", "--diagonal", "--jump",
+    ]);
+    let input_path = positional.first()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_data_path("input/puzzle16.txt"));
+
+    let setup = || -> Result<(String, CostModel, usize), String> {
+        let input = read_to_string(&input_path)
+            .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))?;
+        let mut costs = CostModel::from_options(&options)?;
+        if let Some(path) = options.get("--legend") {
+            let legend = read_to_string(path).map_err(|err| format!("Failed to read {path}: {err}"))?;
+            costs.load_legend(&legend)?;
+        }
+        let route_limit = match options.get("--routes") {
+            Some(value) => value.parse::<usize>().map_err(|_| format!("Invalid --routes value: {value}"))?,
+            None => 0,
+        };
+        Ok((input, costs, route_limit))
+    };
+    let (input, costs, route_limit) = setup().unwrap_or_else(|err| {
+        eprintln!("Error: {err}");
+        exit(1);
+    });
+
     use std::time::Instant;
     let before = Instant::now();
-    basic();
-    advanced();
+    basic(&input, &costs);
+    if let Err(err) = advanced(&input, &costs, route_limit, options.get("--csv").copied()) {
+        eprintln!("Error: {err}");
+        exit(1);
+    }
     println!("Elapsed time: {:.2?}", before.elapsed());
 }
 
-fn basic() {
-    let input = read_to_string(get_data_path("input/puzzle16.txt")).unwrap();
+fn basic(input: &str, costs: &CostModel) {
     let maze = Grid::from_lines(
         &input.lines()
             .filter(|line| !line.is_empty())
@@ -18,7 +55,7 @@ fn basic() {
             .collect::<Vec<String>>()
     ).unwrap();
 
-    let graph = MazeGraph::new(&maze);
+    let graph = MazeGraph::new(&maze, costs);
     let mut astar = AStar::new(graph);
     loop {
         if astar.next() {
@@ -49,9 +86,8 @@ fn basic() {
     }
 }
 
-fn advanced() {
-    let input = read_to_string(get_data_path("input/puzzle16.txt")).unwrap();
-    let mut maze = Grid::from_lines(
+fn advanced(input: &str, costs: &CostModel, route_limit: usize, csv_path: Option<&str>) -> Result<(), String> {
+    let maze = Grid::from_lines(
         &input.lines()
             .filter(|line| !line.is_empty())
             .map(|line| line.into())
@@ -59,9 +95,10 @@ fn advanced() {
     ).unwrap();
 
     let goal = maze.find(&'E').unwrap();
-    maze.set(goal, '.');
 
-    let graph = MazeGraph::new(&maze);
+    // Explore the whole maze, so that every optimal way of reaching the goal is recorded
+    let mut graph = MazeGraph::new(&maze, costs);
+    graph.end = None;
     let mut astar = AStar::new(graph);
     loop {
         if astar.next() {
@@ -69,6 +106,7 @@ fn advanced() {
         }
     }
 
+    let start = maze.find(&'S').unwrap();
     let mut best_paths = maze.clone();
     let mut to_visit = vec![
         (goal.0, goal.1, Direction::North),
@@ -77,10 +115,10 @@ fn advanced() {
         (goal.0, goal.1, Direction::West),
     ];
 
-    let min_cost = to_visit.iter().map(|key| astar.get_cost(key)).min();
-    if let Some(min_cost) = min_cost {
-        to_visit.retain(|key| astar.get_cost(key) == min_cost);
-    }
+    let min_cost = to_visit.iter().filter_map(|key| astar.get_cost(key)).min();
+    to_visit.retain(|key| min_cost.is_some() && astar.get_cost(key) == min_cost);
+
+    let optimal_paths = OptimalPaths::collect(&astar, (start.0, start.1, Direction::East), &to_visit)?;
 
     while let Some(to_key) = to_visit.pop() {
         best_paths.set((to_key.0, to_key.1), 'O');
@@ -107,11 +145,68 @@ fn advanced() {
     }
 
     println!("Best paths tile count (advanced): {tile_count}");
+    println!("Best paths count (advanced): {}", optimal_paths.count());
+
+    let usage = optimal_paths.tile_usage(maze.width(), maze.height())?;
+    let io_error = |err: std::io::Error| format!("Failed to write heatmap: {err}");
+    let mut heatmap_writer = LineWriter::new(
+        File::create(get_data_path("output/puzzle16_heatmap.txt")).map_err(io_error)?
+    );
+    for line in paths::heatmap_lines(&maze, &usage) {
+        writeln!(heatmap_writer, "{line}").map_err(io_error)?;
+    }
+
+    if let Some(csv_path) = csv_path {
+        let io_error = |err: std::io::Error| format!("Failed to write {csv_path}: {err}");
+        let mut csv_writer = LineWriter::new(File::create(csv_path).map_err(io_error)?);
+        writeln!(csv_writer, "x,y,paths").map_err(io_error)?;
+        for y in 0..usage.height() {
+            for x in 0..usage.width() {
+                let count = usage.get((x, y)).unwrap();
+                if count > 0 {
+                    writeln!(csv_writer, "{x},{y},{count}").map_err(io_error)?;
+                }
+            }
+        }
+    }
+
+    for (i, route) in optimal_paths.routes(route_limit).iter().enumerate() {
+        println!("Route #{}: {route}", i + 1);
+    }
+    Ok(())
 }
 
-#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 enum Direction { North, East, South, West }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Move { Forward, TurnRight, TurnLeft, TurnAround, DiagonalLeft, DiagonalRight, Jump }
+
+const MOVES: [Move; 7] = [
+    Move::Forward, Move::TurnRight, Move::TurnLeft, Move::TurnAround,
+    Move::DiagonalLeft, Move::DiagonalRight, Move::Jump,
+];
+
+impl Move {
+    fn to_char(self) -> char {
+        match self {
+            Move::Forward => 'F',
+            Move::TurnRight => 'R',
+            Move::TurnLeft => 'L',
+            Move::TurnAround => 'U',
+            Move::DiagonalLeft => 'l',
+            Move::DiagonalRight => 'r',
+            Move::Jump => 'J',
+        }
+    }
+
+    fn changes_tile(self) -> bool {
+        !matches!(self, Move::TurnRight | Move::TurnLeft | Move::TurnAround)
+    }
+}
+
+type MazeKey = (i32, i32, Direction);
+
 impl Direction {
     fn turn_right(&self) -> Direction {
         match self {
@@ -160,13 +255,14 @@ impl Debug for MazeNode {
 
 struct MazeGraph<'a> {
     maze: &'a Grid<char>,
+    costs: &'a CostModel,
     start: (i32, i32),
     end: Option<(i32, i32)>,
     debug: bool,
 }
 
 impl AStarNode for MazeNode {
-    type Key = (i32, i32, Direction);
+    type Key = MazeKey;
 
     fn key(&self) -> Self::Key {
         (self.x, self.y, self.direction)
@@ -174,15 +270,15 @@ impl AStarNode for MazeNode {
 }
 
 impl<'a> MazeGraph<'a> {
-    fn new(maze: &Grid<char>) -> MazeGraph {
+    fn new(maze: &'a Grid<char>, costs: &'a CostModel) -> MazeGraph<'a> {
         let start = maze.find(&'S').unwrap();
         let end = maze.find(&'E');
-        MazeGraph { maze, start, end, debug: false }
+        MazeGraph { maze, costs, start, end, debug: false }
     }
 }
 
 impl<'a> AStarGraph<MazeNode> for MazeGraph<'a> {
-    type Edge = ();
+    type Edge = Move;
     type Cost = i32;
 
     fn start(&self) -> MazeNode {
@@ -190,9 +286,10 @@ impl<'a> AStarGraph<MazeNode> for MazeGraph<'a> {
         MazeNode { x, y, direction: Direction::East }
     }
 
-    fn neighbors(&self, node: &MazeNode) -> impl Iterator<Item = (MazeNode, (), Self::Cost)> + '_ {
+    fn neighbors(&self, node: &MazeNode) -> impl Iterator<Item = (MazeNode, Move, Self::Cost)> + '_ {
         MazeNeighbors {
-            maze: &self.maze,
+            maze: self.maze,
+            costs: self.costs,
             from_x: node.x,
             from_y: node.y,
             direction: node.direction,
@@ -202,13 +299,7 @@ impl<'a> AStarGraph<MazeNode> for MazeGraph<'a> {
 
     fn estimate(&self, node: &MazeNode) -> Self::Cost {
         if let Some((end_x, end_y)) = self.end {
-            let dx = end_x - node.x;
-            let dy = end_y - node.y;
-            let mut cost = dx.abs() + dy.abs();
-            if dx != 0 || dy != 0 {
-                cost += 1000;
-            }
-            return cost;
+            return self.costs.estimate((end_x - node.x, end_y - node.y), node.direction.offset());
         }
         return 0;
     }
@@ -235,48 +326,50 @@ impl<'a> AStarGraph<MazeNode> for MazeGraph<'a> {
 
 struct MazeNeighbors<'a> {
     maze: &'a Grid<char>,
+    costs: &'a CostModel,
     from_x: i32,
     from_y: i32,
     direction: Direction,
     index: usize,
 }
 
+impl<'a> MazeNeighbors<'a> {
+    fn apply(&self, step: Move) -> Option<(MazeNode, Move, i32)> {
+        let move_cost = self.costs.move_cost(step)?;
+        let (fx, fy) = self.direction.offset();
+        let (lx, ly) = self.direction.turn_left().offset();
+        let (offset, direction) = match step {
+            Move::Forward => ((fx, fy), self.direction),
+            Move::TurnRight => ((0, 0), self.direction.turn_right()),
+            Move::TurnLeft => ((0, 0), self.direction.turn_left()),
+            Move::TurnAround => ((0, 0), self.direction.turn_right().turn_right()),
+            Move::DiagonalLeft => ((fx + lx, fy + ly), self.direction),
+            Move::DiagonalRight => ((fx - lx, fy - ly), self.direction),
+            Move::Jump => {
+                let (distance, _) = self.costs.jump?;
+                ((fx * distance, fy * distance), self.direction)
+            }
+        };
+        let (x, y) = (self.from_x + offset.0, self.from_y + offset.1);
+        let terrain_cost = if step.changes_tile() {
+            self.costs.terrain_cost(self.maze.get((x, y))?)?
+        } else {
+            0
+        };
+        Some((MazeNode { x, y, direction }, step, move_cost + terrain_cost))
+    }
+}
+
 impl<'a> Iterator for MazeNeighbors<'a> {
-    type Item = (MazeNode, (), i32);
+    type Item = (MazeNode, Move, i32);
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.index {
-                0 => {
-                    self.index += 1;
-                    let (dx, dy) = self.direction.offset();
-                    let next = (self.from_x + dx, self.from_y + dy);
-                    if let Some('.' | 'S' | 'E') = self.maze.get(next) {
-                        let node = MazeNode {
-                            x: next.0,
-                            y: next.1,
-                            direction: self.direction,
-                        };
-                        return Some((node, (), 1));
-                    }
-                }
-                1 | 2 => {
-                    let direction = if self.index == 1 {
-                        self.direction.turn_right()
-                    } else {
-                        self.direction.turn_left()
-                    };
-                    self.index += 1;
-                    let node = MazeNode {
-                        x: self.from_x,
-                        y: self.from_y,
-                        direction,
-                    };
-                    return Some((node, (), 1000));
-                }
-                _ => { break; }
+        while let Some(&step) = MOVES.get(self.index) {
+            self.index += 1;
+            if let Some(neighbor) = self.apply(step) {
+                return Some(neighbor);
             }
         }
-        return None;
+        None
     }
 }
@@ -0,0 +1,209 @@
+use core::{AStar, Grid};
+use std::collections::{HashMap, HashSet};
+
+use super::{MazeGraph, MazeKey, MazeNode, Move};
+
+pub struct OptimalPaths {
+    order: Vec<MazeKey>,
+    predecessors: HashMap<MazeKey, Vec<(MazeKey, Move)>>,
+    successors: HashMap<MazeKey, Vec<(MazeKey, Move)>>,
+    from_start: HashMap<MazeKey, u128>,
+    to_goal: HashMap<MazeKey, u128>,
+    start: MazeKey,
+    goals: Vec<MazeKey>,
+}
+
+impl OptimalPaths {
+    pub fn collect(astar: &AStar<MazeNode, MazeGraph>, start: MazeKey, goals: &[MazeKey]) -> Result<OptimalPaths, String> {
+        let mut predecessors: HashMap<MazeKey, Vec<(MazeKey, Move)>> = HashMap::new();
+        let mut successors: HashMap<MazeKey, Vec<(MazeKey, Move)>> = HashMap::new();
+        let mut seen: HashSet<MazeKey> = goals.iter().copied().collect();
+        let mut to_visit = goals.to_vec();
+        while let Some(to) = to_visit.pop() {
+            for &(from, edge) in astar.get_from(&to) {
+                predecessors.entry(to).or_default().push((from, edge));
+                successors.entry(from).or_default().push((to, edge));
+                if seen.insert(from) {
+                    to_visit.push(from);
+                }
+            }
+        }
+
+        // Every move has a positive cost, so ordering by path cost is a topological order of the DAG
+        let mut order: Vec<MazeKey> = seen.into_iter().collect();
+        order.sort_by_key(|key| (astar.get_cost(key), *key));
+
+        let overflow = || "Path count does not fit into 128 bits".to_string();
+        let mut from_start: HashMap<MazeKey, u128> = HashMap::new();
+        for key in order.iter() {
+            let mut count = u128::from(*key == start);
+            for (from, _) in predecessors.get(key).into_iter().flatten() {
+                count = count.checked_add(from_start[from]).ok_or_else(overflow)?;
+            }
+            from_start.insert(*key, count);
+        }
+        let mut to_goal: HashMap<MazeKey, u128> = HashMap::new();
+        for key in order.iter().rev() {
+            let mut count = u128::from(goals.contains(key));
+            for (to, _) in successors.get(key).into_iter().flatten() {
+                count = count.checked_add(to_goal[to]).ok_or_else(overflow)?;
+            }
+            to_goal.insert(*key, count);
+        }
+
+        Ok(OptimalPaths {
+            order,
+            predecessors,
+            successors,
+            from_start,
+            to_goal,
+            start,
+            goals: goals.to_vec(),
+        })
+    }
+
+    pub fn count(&self) -> u128 {
+        self.goals.iter().map(|goal| self.from_start.get(goal).copied().unwrap_or(0)).sum()
+    }
+
+    pub fn tile_usage(&self, width: i32, height: i32) -> Result<Grid<u128>, String> {
+        let mut usage = Grid::new(width, height, 0u128);
+        for key in self.order.iter() {
+            // Count each path once per tile, at the state where it enters the tile
+            let entering = self.predecessors.get(key).into_iter()
+                .flatten()
+                .filter(|(_, edge)| edge.changes_tile())
+                .try_fold(u128::from(*key == self.start), |total, (from, _)| total.checked_add(self.from_start[from]));
+            let through = entering
+                .and_then(|entering| entering.checked_mul(self.to_goal[key]))
+                .and_then(|through| through.checked_add(usage.get((key.0, key.1)).unwrap()))
+                .ok_or("Tile usage does not fit into 128 bits")?;
+            usage.set((key.0, key.1), through);
+        }
+        Ok(usage)
+    }
+
+    pub fn routes(&self, limit: usize) -> Vec<String> {
+        let mut routes = Vec::new();
+        let mut stack = vec![(self.start, String::new())];
+        while let Some((key, route)) = stack.pop() {
+            if routes.len() >= limit {
+                break;
+            }
+            if self.goals.contains(&key) {
+                routes.push(route.clone());
+            }
+            for (to, edge) in self.successors.get(&key).into_iter().flatten().rev() {
+                if self.to_goal[to] > 0 {
+                    let mut next = route.clone();
+                    next.push(edge.to_char());
+                    stack.push((*to, next));
+                }
+            }
+        }
+        routes
+    }
+}
+
+pub fn heatmap_lines(maze: &Grid<char>, usage: &Grid<u128>) -> Vec<String> {
+    let max = (0..usage.height())
+        .flat_map(|y| (0..usage.width()).map(move |x| (x, y)))
+        .map(|at| usage.get(at).unwrap())
+        .max()
+        .unwrap_or(0);
+    (0..maze.height())
+        .map(|y| {
+            (0..maze.width())
+                .map(|x| match (maze.get((x, y)).unwrap(), usage.get((x, y)).unwrap()) {
+                    ('#', _) => '#',
+                    (_, 0) => '.',
+                    (_, count) => {
+                        let level = (count as f64 / max as f64 * 9.0).ceil().max(1.0);
+                        char::from_digit(level as u32, 10).unwrap()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_model::CostModel;
+    use crate::Direction;
+
+    const SMALL_MAZE: &str = "\
+###############
+#.......#....E#
+#.#.###.#.###.#
+#.....#.#...#.#
+#.###.#####.#.#
+#.#.#.......#.#
+#.#.#####.###.#
+#...........#.#
+###.#.#####.#.#
+#...#.....#.#.#
+#.#.#.###.#.#.#
+#.....#...#.#.#
+#.###.#.#.#.#.#
+#S..#.....#...#
+###############";
+
+    const LARGE_MAZE: &str = "\
+#################
+#...#...#...#..E#
+#.#.#.#.#.#.#.#.#
+#.#.#.#...#...#.#
+#.#.#.#.###.#.#.#
+#...#.#.#.....#.#
+#.#.#.#.#.#####.#
+#.#...#.#.#.....#
+#.#.#####.#.###.#
+#.#.#.......#...#
+#.#.###.#####.###
+#.#.#...#.....#.#
+#.#.#.#####.###.#
+#.#.#.........#.#
+#.#.#.#########.#
+#S#.............#
+#################";
+
+    fn optimal_paths(input: &str, costs: &CostModel) -> (Grid<char>, OptimalPaths) {
+        let maze = Grid::from_lines(&input.lines().map(String::from).collect::<Vec<String>>()).unwrap();
+        let mut graph = MazeGraph::new(&maze, costs);
+        graph.end = None;
+        let mut astar = AStar::new(graph);
+        while !astar.next() {}
+
+        let (end_x, end_y) = maze.find(&'E').unwrap();
+        let mut goals: Vec<MazeKey> = [Direction::North, Direction::East, Direction::South, Direction::West]
+            .into_iter()
+            .map(|direction| (end_x, end_y, direction))
+            .collect();
+        let min_cost = goals.iter().filter_map(|key| astar.get_cost(key)).min();
+        goals.retain(|key| astar.get_cost(key) == min_cost);
+
+        let (start_x, start_y) = maze.find(&'S').unwrap();
+        let paths = OptimalPaths::collect(&astar, (start_x, start_y, Direction::East), &goals).unwrap();
+        (maze, paths)
+    }
+
+    fn tile_count(usage: &Grid<u128>) -> usize {
+        (0..usage.height())
+            .flat_map(|y| (0..usage.width()).map(move |x| (x, y)))
+            .filter(|&at| usage.get(at).unwrap() > 0)
+            .count()
+    }
+
+    #[test]
+    fn counts_paths_and_tiles_on_samples() {
+        let costs = CostModel::default();
+        for (input, expected_paths, expected_tiles) in [(SMALL_MAZE, 3, 45), (LARGE_MAZE, 2, 64)] {
+            let (maze, paths) = optimal_paths(input, &costs);
+            let usage = paths.tile_usage(maze.width(), maze.height()).unwrap();
+            assert_eq!(paths.count(), expected_paths);
+            assert_eq!(tile_count(&usage), expected_tiles);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use super::{Operand, Register};
+use super::machine::{Action, InstructionSet, OperandMode};
+
+#[derive(Copy, Clone, PartialEq)]
+enum OperandKind {
+    Literal,
+    Combo,
+    Target,
+}
+
+enum ParsedOperand {
+    Resolved(Operand),
+    Label(String),
+}
+
+struct ParsedInstruction {
+    line: usize,
+    code: u8,
+    operand: ParsedOperand,
+}
+
+pub fn assemble(isa: &InstructionSet, source: &str) -> Result<Vec<u8>, String> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut instructions: Vec<ParsedInstruction> = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let mut text = strip_comment(line).trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let offset = instructions.len() * 2;
+            define_label(&mut labels, label.trim(), offset)
+                .map_err(|err| format!("Line {line_number}: {err}"))?;
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (code, operand) = parse_instruction(isa, text)
+            .map_err(|err| format!("Line {line_number}: {err}"))?;
+        instructions.push(ParsedInstruction { line: line_number, code, operand });
+    }
+
+    let mut program = Vec::new();
+    for instruction in instructions {
+        let operand = match instruction.operand {
+            ParsedOperand::Resolved(operand) => operand,
+            ParsedOperand::Label(label) => {
+                let offset = *labels.get(&label)
+                    .ok_or(format!("Line {}: Undefined label #{label}", instruction.line))?;
+                let offset = u8::try_from(offset)
+                    .ok()
+                    .filter(|&offset| offset <= 7)
+                    .ok_or(format!("Line {}: Label #{label} at offset {offset} is out of 3-bit jump range", instruction.line))?;
+                Operand::Literal(offset)
+            }
+        };
+        program.push(instruction.code);
+        program.push(encode_operand(&operand));
+    }
+    Ok(program)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find(';'), line.find("//")].into_iter().flatten().min();
+    match end {
+        Some(end) => &line[..end],
+        None => line,
+    }
+}
+
+fn define_label(labels: &mut HashMap<String, usize>, label: &str, offset: usize) -> Result<(), String> {
+    let name = label.strip_prefix('#').unwrap_or(label);
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("Invalid label: {label}"));
+    }
+    if let Ok(number) = name.parse::<usize>() {
+        if number != offset {
+            return Err(format!("Label #{number} does not match instruction offset {offset}"));
+        }
+        return Ok(());
+    }
+    if labels.insert(name.to_string(), offset).is_some() {
+        return Err(format!("Duplicate label: {name}"));
+    }
+    Ok(())
+}
+
+fn parse_instruction(isa: &InstructionSet, text: &str) -> Result<(u8, ParsedOperand), String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (mnemonic, rest) = (words[0], words[1..].join(" "));
+    let (code, spec) = isa.find(mnemonic).ok_or(format!("Unknown instruction: {mnemonic}"))?;
+    let kind = match (spec.action, spec.operand) {
+        (Action::Jnz { .. }, OperandMode::Literal) => OperandKind::Target,
+        (_, OperandMode::Combo) => OperandKind::Combo,
+        _ => OperandKind::Literal,
+    };
+    let template = spec.action.template();
+
+    let operand_text = if rest.is_empty() && spec.operand == OperandMode::Ignored {
+        "0"
+    } else if !rest.contains(' ') {
+        rest.as_str()
+    } else {
+        let (prefix, suffix) = template.split_once("{}").unwrap();
+        let short_template = template.split(" (").next().unwrap();
+        if let Some(operand) = rest.strip_prefix(prefix).and_then(|rest| rest.strip_suffix(suffix)) {
+            operand
+        } else if rest == short_template {
+            "0"
+        } else {
+            return Err(format!("Expected '{mnemonic} {template}' but found '{text}'"));
+        }
+    };
+    if operand_text.is_empty() {
+        return Err(format!("Missing operand for {mnemonic}"));
+    }
+
+    let operand = parse_operand(operand_text, kind)?;
+    Ok((code, operand))
+}
+
+fn parse_operand(text: &str, kind: OperandKind) -> Result<ParsedOperand, String> {
+    if text.starts_with("unused") {
+        return Err(format!("Operand {text} is reserved and cannot be encoded"));
+    }
+    match kind {
+        OperandKind::Combo => {
+            let operand = match text {
+                "A" => Operand::Register(Register::A),
+                "B" => Operand::Register(Register::B),
+                "C" => Operand::Register(Register::C),
+                _ => match text.parse::<u8>() {
+                    Ok(value @ 0..=3) => Operand::Literal(value),
+                    _ => {
+                        return Err(format!("Invalid combo operand {text}: expected 0-3, A, B or C"));
+                    }
+                },
+            };
+            Ok(ParsedOperand::Resolved(operand))
+        }
+        OperandKind::Literal => match text.parse::<u8>() {
+            Ok(value @ 0..=7) => Ok(ParsedOperand::Resolved(Operand::Literal(value))),
+            _ => Err(format!("Invalid literal operand {text}: expected 0-7")),
+        },
+        OperandKind::Target => {
+            let target = text.strip_prefix('#').unwrap_or(text);
+            match target.parse::<u8>() {
+                Ok(value @ 0..=7) => Ok(ParsedOperand::Resolved(Operand::Literal(value))),
+                Ok(_) => Err(format!("Invalid jump target {text}: expected 0-7")),
+                Err(_) => Ok(ParsedOperand::Label(target.to_string())),
+            }
+        }
+    }
+}
+
+fn encode_operand(operand: &Operand) -> u8 {
+    match operand {
+        Operand::Literal(value) | Operand::Ignored(value) | Operand::Unused(value) => *value,
+        Operand::Register(Register::A) => 4,
+        Operand::Register(Register::B) => 5,
+        Operand::Register(Register::C) => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disassemble, parse_program};
+
+    fn round_trip(program: &str) {
+        let isa = InstructionSet::standard();
+        let program = parse_program(program).unwrap();
+        let mut listing = Vec::new();
+        disassemble(&mut listing, &isa, &program).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        assert_eq!(assemble(&isa, &listing), Ok(program), "{listing}");
+    }
+
+    #[test]
+    fn assembles_disassembled_programs() {
+        round_trip("0,1,5,4,3,0");
+        round_trip("0,3,5,4,3,0");
+        round_trip("2,4,1,5,7,5,1,6,0,3,4,0,5,5,3,0");
+        // Jumps into the middle of the program and onto themselves
+        round_trip("2,4,1,1,3,4,5,5,0,3,3,2");
+    }
+
+    #[test]
+    fn resolves_named_labels() {
+        let source = "    bst A % 8 -> B\nloop: out B % 8 ; print\n    adv A >> 3 -> A\n    jnz if A goto #loop\n";
+        assert_eq!(assemble(&InstructionSet::standard(), source), Ok(vec![2, 4, 5, 5, 0, 3, 3, 2]));
+    }
+
+    #[test]
+    fn reports_unknown_mnemonic() {
+        let source = "    adv A >> 3 -> A\n    mul A\n";
+        assert_eq!(
+            assemble(&InstructionSet::standard(), source),
+            Err("Line 2: Unknown instruction: mul".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_undefined_label() {
+        let source = "start: adv A >> 3 -> A\n    jnz if A goto #loop\n";
+        assert_eq!(
+            assemble(&InstructionSet::standard(), source),
+            Err("Line 2: Undefined label #loop".to_string())
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::thread;
+
+use super::{Instruction, Operand};
+use super::machine::{Action, InstructionSet};
+
+#[derive(Copy, Clone)]
+enum Source { Const(u64), Register(usize) }
+
+// Operations with the registers and operand of their instruction resolved to indices and constants
+#[derive(Copy, Clone)]
+enum Op {
+    Shr { dest: usize, src: usize, shift: Source },
+    Xor { dest: usize, value: Source },
+    Mod8 { dest: usize, value: Source },
+    Set { dest: usize, value: Source },
+    Jnz { test: usize, target: Source },
+    Out(Source),
+    In,
+    Halt,
+    Invalid,
+}
+
+pub struct CompiledProgram {
+    ops: Vec<Op>,
+    cycle_limit: Option<u64>,
+}
+
+impl CompiledProgram {
+    pub fn compile(isa: &InstructionSet, program: &[u8]) -> CompiledProgram {
+        // Jumps may target any byte offset, so decode an instruction starting at every offset
+        let ops = (0..program.len())
+            .map(|index| {
+                let raw_operand = program.get(index + 1).copied();
+                match raw_operand.and_then(|raw_operand| isa.decode(program[index], raw_operand)) {
+                    Some(instruction) => Self::compile_instruction(instruction),
+                    None => Op::Invalid,
+                }
+            })
+            .collect();
+        CompiledProgram { ops, cycle_limit: None }
+    }
+
+    pub fn with_cycle_limit(mut self, cycle_limit: u64) -> CompiledProgram {
+        self.cycle_limit = Some(cycle_limit);
+        self
+    }
+
+    fn compile_instruction(Instruction(spec, operand): Instruction) -> Op {
+        let source = match operand {
+            Operand::Literal(value) => Source::Const(value.into()),
+            Operand::Register(register) => Source::Register(register as usize),
+            Operand::Ignored(_) => Source::Const(0),
+            Operand::Unused(_) => {
+                return Op::Invalid;
+            }
+        };
+        match spec.action {
+            Action::Shr { dest, src } => Op::Shr { dest: dest as usize, src: src as usize, shift: source },
+            Action::Xor { dest } => Op::Xor { dest: dest as usize, value: source },
+            Action::XorRegister { dest, src } => Op::Xor { dest: dest as usize, value: Source::Register(src as usize) },
+            Action::Mod8 { dest } => Op::Mod8 { dest: dest as usize, value: source },
+            Action::Set { dest } => Op::Set { dest: dest as usize, value: source },
+            Action::Jnz { test } => Op::Jnz { test: test as usize, target: source },
+            Action::Out => Op::Out(source),
+            Action::In { .. } => Op::In,
+            Action::Halt => Op::Halt,
+        }
+    }
+
+    pub fn run(&self, mut registers: [u64; 3], output: &mut Vec<u8>) -> Result<(), String> {
+        let mut index = 0;
+        let mut cycles: u64 = 0;
+        while let Some(&op) = self.ops.get(index) {
+            if let Some(cycle_limit) = self.cycle_limit {
+                if cycles >= cycle_limit {
+                    return Err(format!("Program did not halt within {cycle_limit} cycles"));
+                }
+                cycles += 1;
+            }
+            let read = |source: Source| match source {
+                Source::Const(value) => value,
+                Source::Register(register) => registers[register],
+            };
+            match op {
+                Op::Shr { dest, src, shift } => registers[dest] = shift_right(registers[src], read(shift)),
+                Op::Xor { dest, value } => registers[dest] ^= read(value),
+                Op::Mod8 { dest, value } => registers[dest] = read(value) % 8,
+                Op::Set { dest, value } => registers[dest] = read(value),
+                Op::Jnz { test, target } => {
+                    if registers[test] != 0 {
+                        index = usize::try_from(read(target))
+                            .map_err(|_| format!("Jump target out of range at #{index}"))?;
+                        continue;
+                    }
+                }
+                Op::Out(source) => output.push((read(source) % 8) as u8),
+                Op::In => {
+                    return Err(format!("Compiled programs take no input, but #{index} reads it"));
+                }
+                Op::Halt => {
+                    return Ok(());
+                }
+                Op::Invalid => {
+                    return Err(format!("Invalid instruction #{index}"));
+                }
+            }
+            index += 2;
+        }
+        Ok(())
+    }
+
+    pub fn run_batch(&self, a_values: &[u64], b: u64, c: u64, threads: usize) -> Vec<Result<Vec<u8>, String>> {
+        let chunk_size = a_values.len().div_ceil(threads.max(1)).max(1);
+        thread::scope(|scope| {
+            let handles: Vec<_> = a_values.chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || {
+                    chunk.iter()
+                        .map(|&a| {
+                            let mut output = Vec::new();
+                            self.run([a, b, c], &mut output).map(|_| output)
+                        })
+                        .collect::<Vec<_>>()
+                }))
+                .collect();
+            handles.into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+fn shift_right(value: u64, shift: u64) -> u64 {
+    u32::try_from(shift).ok()
+        .and_then(|shift| value.checked_shr(shift))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComputerDescription, ComputerState};
+
+    const PROGRAMS: [&str; 3] = ["0,1,5,4,3,0", "0,3,5,4,3,0", "2,4,1,5,7,5,1,6,0,3,4,0,5,5,3,0"];
+
+    fn interpreter(program: &str) -> impl Fn(u64) -> Vec<u8> {
+        let content = format!("Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: {program}\n");
+        let description: ComputerDescription = ComputerDescription::parse(&content).unwrap();
+        move |a| {
+            let mut state = ComputerState::new();
+            let mut output = Vec::new();
+            description.initialize(&mut state);
+            state.registers[0] = a;
+            description.run(&mut state, None, |n| output.push(n as u8)).unwrap();
+            output
+        }
+    }
+
+    fn compile_standard(program: &str) -> CompiledProgram {
+        CompiledProgram::compile(&InstructionSet::standard(), &crate::parse_program(program).unwrap())
+    }
+
+    #[test]
+    fn runs_sample_programs() {
+        let mut output = Vec::new();
+        compile_standard(PROGRAMS[0]).run([729, 0, 0], &mut output).unwrap();
+        assert_eq!(output, [4, 6, 3, 5, 6, 3, 5, 2, 1, 0]);
+        let mut output = Vec::new();
+        compile_standard(PROGRAMS[1]).run([117440, 0, 0], &mut output).unwrap();
+        assert_eq!(output, [0, 3, 5, 4, 3, 0]);
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        for program in PROGRAMS {
+            let compiled = compile_standard(program);
+            let interpret = interpreter(program);
+            for a in (0..2000).chain([729, 117440, 66752888]) {
+                let mut output = Vec::new();
+                compiled.run([a, 0, 0], &mut output).unwrap();
+                assert_eq!(output, interpret(a), "program {program} with register A {a}");
+            }
+        }
+    }
+
+    #[test]
+    fn runs_batches_on_several_threads() {
+        let a_values: Vec<u64> = (0..1000).map(|a| a * 117).collect();
+        for program in PROGRAMS {
+            let compiled = compile_standard(program);
+            let interpret = interpreter(program);
+            let expected: Vec<Result<Vec<u8>, String>> = a_values.iter()
+                .map(|&a| Ok(interpret(a)))
+                .collect();
+            for threads in [1, 3, 8] {
+                assert_eq!(compiled.run_batch(&a_values, 0, 0, threads), expected, "program {program} on {threads} threads");
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_cycle_limit() {
+        let compiled = compile_standard(PROGRAMS[0]).with_cycle_limit(5);
+        let mut output = Vec::new();
+        assert_eq!(
+            compiled.run([729, 0, 0], &mut output),
+            Err("Program did not halt within 5 cycles".to_string())
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{sink, BufRead, BufWriter, Write};
+
+use super::{ComputerDescription, ComputerState, Register};
+use super::machine::{parse_register, Word};
+
+pub enum StopReason<W: Word> {
+    Halted,
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint(Register, W, W),
+    CycleLimit(u64),
+}
+
+// Where executed instructions are traced: the writer the commands report to, or a file
+enum Trace<'a> {
+    Off,
+    Commands,
+    Writer(Box<dyn Write + 'a>),
+}
+
+pub struct Debugger<'a, W: Word> {
+    description: &'a ComputerDescription<W>,
+    state: ComputerState<W>,
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<Register>,
+    max_cycles: Option<u64>,
+    cycles: u64,
+    output: Vec<u64>,
+    trace: Trace<'a>,
+}
+
+impl<'a, W: Word> Debugger<'a, W> {
+    pub fn new(description: &'a ComputerDescription<W>, max_cycles: Option<u64>) -> Debugger<'a, W> {
+        let mut state = ComputerState::new();
+        description.initialize(&mut state);
+        Debugger {
+            description,
+            state,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            max_cycles,
+            cycles: 0,
+            output: Vec::new(),
+            trace: Trace::Off,
+        }
+    }
+
+    pub fn set_trace(&mut self, writer: Option<Box<dyn Write + 'a>>) {
+        self.trace = match writer {
+            Some(writer) => Trace::Writer(writer),
+            None => Trace::Off,
+        };
+    }
+
+    pub fn reset(&mut self) {
+        self.description.initialize(&mut self.state);
+        self.cycles = 0;
+        self.output.clear();
+    }
+
+    pub fn output(&self) -> &[u64] {
+        &self.output
+    }
+
+    pub fn step(&mut self, out: &mut dyn Write) -> Result<Option<StopReason<W>>, String> {
+        if let Some(max_cycles) = self.max_cycles {
+            if self.cycles >= max_cycles {
+                return Ok(Some(StopReason::CycleLimit(max_cycles)));
+            }
+        }
+
+        let index = self.state.instruction;
+        let watched: Vec<W> = self.watches.iter().map(|&register| self.state.get(register).clone()).collect();
+        let mut printed = None;
+        let output = &mut self.output;
+        let executed = self.description.step(&mut self.state, &mut |n| {
+            output.push(n);
+            printed = Some(n);
+        })?;
+        if !executed {
+            return Ok(Some(StopReason::Halted));
+        }
+        self.cycles += 1;
+
+        let writer: Option<&mut dyn Write> = match &mut self.trace {
+            Trace::Off => None,
+            Trace::Commands => Some(out),
+            Trace::Writer(writer) => Some(writer.as_mut()),
+        };
+        if let Some(writer) = writer {
+            let instruction = self.description.instruction_at(index)
+                .map(|instruction| instruction.to_string())
+                .unwrap_or_default();
+            write!(writer, "{:>8} #{:<3} {:<32} {}", self.cycles, index, instruction, format_registers(&self.state))
+                .map_err(|err| format!("Failed to write trace: {err}"))?;
+            if let Some(n) = printed {
+                write!(writer, " out={n}").map_err(|err| format!("Failed to write trace: {err}"))?;
+            }
+            writeln!(writer).map_err(|err| format!("Failed to write trace: {err}"))?;
+        }
+
+        for (&register, before) in self.watches.iter().zip(watched) {
+            let after = self.state.get(register).clone();
+            if after != before {
+                return Ok(Some(StopReason::Watchpoint(register, before, after)));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn run(&mut self, steps: Option<u64>, out: &mut dyn Write) -> Result<StopReason<W>, String> {
+        let mut executed = 0;
+        loop {
+            if let Some(reason) = self.step(out)? {
+                return Ok(reason);
+            }
+            executed += 1;
+            if steps.is_some_and(|steps| executed >= steps) {
+                return Ok(StopReason::Stepped);
+            }
+            if self.breakpoints.contains(&self.state.instruction) {
+                return Ok(StopReason::Breakpoint(self.state.instruction));
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let location = match self.description.instruction_at(self.state.instruction) {
+            Some(instruction) => format!("#{}: {}", self.state.instruction, instruction),
+            None => String::from("halted"),
+        };
+        format!("{} | {} | cycles {}", location, format_registers(&self.state), self.cycles)
+    }
+
+    pub fn run_commands<R: BufRead, O: Write>(&mut self, input: R, out: &mut O, echo: bool) -> Result<(), String> {
+        let write_error = |err: std::io::Error| format!("Failed to write debugger output: {err}");
+        if !echo {
+            write!(out, "(dbg) ").map_err(write_error)?;
+            out.flush().map_err(write_error)?;
+        }
+        for line in input.lines() {
+            let line = line.map_err(|err| format!("Failed to read debugger command: {err}"))?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if echo && !parts.is_empty() {
+                writeln!(out, "(dbg) {}", line.trim()).map_err(write_error)?;
+            }
+            match self.execute_command(&parts, out) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Ok(());
+                }
+                Err(err) => {
+                    writeln!(out, "Error: {err}").map_err(write_error)?;
+                }
+            }
+            if !echo {
+                write!(out, "(dbg) ").map_err(write_error)?;
+                out.flush().map_err(write_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_command<O: Write>(&mut self, parts: &[&str], out: &mut O) -> Result<bool, String> {
+        let write_error = |err: std::io::Error| format!("Failed to write debugger output: {err}");
+        match parts {
+            [] => {}
+            ["break" | "b", index] => {
+                let index = parse_index(index)?;
+                self.breakpoints.insert(index);
+                writeln!(out, "Breakpoint set at #{index}").map_err(write_error)?;
+            }
+            ["delete" | "d", index] => {
+                let index = parse_index(index)?;
+                if self.breakpoints.remove(&index) {
+                    writeln!(out, "Breakpoint removed at #{index}").map_err(write_error)?;
+                } else {
+                    writeln!(out, "No breakpoint at #{index}").map_err(write_error)?;
+                }
+            }
+            ["watch" | "w", register] => {
+                let register = parse_register(register)?;
+                if !self.watches.contains(&register) {
+                    self.watches.push(register);
+                }
+                writeln!(out, "Watching register {register}").map_err(write_error)?;
+            }
+            ["unwatch", register] => {
+                let register = parse_register(register)?;
+                self.watches.retain(|&watched| watched != register);
+                writeln!(out, "Stopped watching register {register}").map_err(write_error)?;
+            }
+            ["step" | "s"] | ["step" | "s", _] => {
+                let count = match parts.get(1) {
+                    Some(count) => count.parse::<u64>().map_err(|_| format!("Invalid step count: {count}"))?,
+                    None => 1,
+                };
+                let reason = self.run(Some(count), out)?;
+                self.report(reason, out)?;
+            }
+            ["continue" | "c"] => {
+                let reason = self.run(None, out)?;
+                self.report(reason, out)?;
+            }
+            ["regs" | "r"] => {
+                writeln!(out, "{}", self.describe()).map_err(write_error)?;
+            }
+            ["output" | "o"] => {
+                let joined = self.output.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(",");
+                writeln!(out, "Output: {joined}").map_err(write_error)?;
+            }
+            ["list" | "l"] => {
+                for (i, instruction) in self.description.isa.decode_program(&self.description.program)?.iter().enumerate() {
+                    let index = i * 2;
+                    let current = if index == self.state.instruction { '>' } else { ' ' };
+                    let breakpoint = if self.breakpoints.contains(&index) { '*' } else { ' ' };
+                    writeln!(out, "{current}{breakpoint} #{index:<3} {instruction}").map_err(write_error)?;
+                }
+            }
+            ["trace", "on"] => {
+                self.trace = Trace::Commands;
+                writeln!(out, "Tracing executed instructions").map_err(write_error)?;
+            }
+            ["trace", "on", path] => {
+                let file = File::create(path).map_err(|err| format!("Failed to create {path}: {err}"))?;
+                self.trace = Trace::Writer(Box::new(BufWriter::new(file)));
+                writeln!(out, "Tracing executed instructions to {path}").map_err(write_error)?;
+            }
+            ["trace", "off"] => {
+                self.trace = Trace::Off;
+            }
+            ["reset"] => {
+                self.reset();
+                writeln!(out, "{}", self.describe()).map_err(write_error)?;
+            }
+            ["help" | "h"] => {
+                writeln!(out, "{}", HELP).map_err(write_error)?;
+            }
+            ["quit" | "q"] => {
+                return Ok(false);
+            }
+            _ => {
+                return Err(format!("Unknown command: {} (type 'help' for the list of commands)", parts.join(" ")));
+            }
+        }
+        Ok(true)
+    }
+
+    fn report<O: Write>(&self, reason: StopReason<W>, out: &mut O) -> Result<(), String> {
+        let message = match reason {
+            StopReason::Halted => String::from("Program halted"),
+            StopReason::Stepped => String::from("Stepped"),
+            StopReason::Breakpoint(index) => format!("Hit breakpoint at #{index}"),
+            StopReason::Watchpoint(register, before, after) => {
+                format!("Register {register} changed: {before:#o} -> {after:#o}")
+            }
+            StopReason::CycleLimit(max_cycles) => format!("Cycle limit of {max_cycles} reached"),
+        };
+        writeln!(out, "{message}\n{}", self.describe())
+            .map_err(|err| format!("Failed to write debugger output: {err}"))
+    }
+}
+
+const HELP: &str = concat!(
+    "break <n>, b <n>       set breakpoint at instruction #n\n",
+    "delete <n>, d <n>      remove breakpoint at instruction #n\n",
+    "watch <r>, w <r>       stop when register A, B or C changes\n",
+    "unwatch <r>            remove register watch\n",
+    "step [n], s [n]        execute n instructions (default 1)\n",
+    "continue, c            run until breakpoint, watchpoint or halt\n",
+    "regs, r                show registers and next instruction\n",
+    "output, o              show program output so far\n",
+    "list, l                list program with breakpoints\n",
+    "trace on [file]|off    print each executed instruction here or to a file\n",
+    "reset                  restart the program\n",
+    "quit, q                exit debugger",
+);
+
+pub fn format_registers<W: Word>(state: &ComputerState<W>) -> String {
+    let [a, b, c] = &state.registers;
+    format!("A={a:#o} B={b:#o} C={c:#o}")
+}
+
+fn parse_index(value: &str) -> Result<usize, String> {
+    value.trim_start_matches('#')
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid instruction index: {value}"))
+}
+
+pub fn trace_program<W: Word, O: Write>(
+    description: &ComputerDescription<W>,
+    writer: O,
+    max_cycles: Option<u64>,
+) -> Result<Vec<u64>, String> {
+    let mut debugger = Debugger::new(description, max_cycles);
+    debugger.set_trace(Some(Box::new(writer)));
+    match debugger.run(None, &mut sink())? {
+        StopReason::CycleLimit(max_cycles) => {
+            Err(format!("Program did not halt within {max_cycles} cycles"))
+        }
+        _ => Ok(debugger.output().to_vec()),
+    }
+}
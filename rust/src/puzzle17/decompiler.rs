@@ -0,0 +1,504 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::{Instruction, Operand, Register};
+use super::machine::{Action, InstructionSet, OpcodeSpec};
+
+#[derive(Clone, PartialEq)]
+enum Expr {
+    Var(String),
+    Const(u64),
+    Mod8(Box<Expr>),
+    Shr(Box<Expr>, Box<Expr>),
+    Xor(Vec<Expr>),
+}
+
+impl Expr {
+    fn var(register: Register) -> Expr {
+        Expr::Var(register_name(register))
+    }
+
+    fn mod8(value: Expr) -> Expr {
+        match value {
+            Expr::Const(x) => Expr::Const(x % 8),
+            Expr::Mod8(_) => value,
+            _ => Expr::Mod8(Box::new(value)),
+        }
+    }
+
+    fn shr(value: Expr, shift: Expr) -> Expr {
+        match (value, shift) {
+            (Expr::Const(x), Expr::Const(y)) => {
+                Expr::Const(x.checked_shr(y.try_into().unwrap_or(u32::MAX)).unwrap_or(0))
+            }
+            (value, Expr::Const(0)) => value,
+            (Expr::Const(0), _) => Expr::Const(0),
+            (value, shift) => Expr::Shr(Box::new(value), Box::new(shift)),
+        }
+    }
+
+    fn xor(left: Expr, right: Expr) -> Expr {
+        let mut terms: Vec<Expr> = Vec::new();
+        let mut constant = 0;
+        for term in [left, right] {
+            let flattened = match term {
+                Expr::Xor(inner) => inner,
+                term => vec![term],
+            };
+            for term in flattened {
+                match term {
+                    Expr::Const(value) => constant ^= value,
+                    term => {
+                        if let Some(index) = terms.iter().position(|existing| *existing == term) {
+                            terms.remove(index);
+                        } else {
+                            terms.push(term);
+                        }
+                    }
+                }
+            }
+        }
+        if constant != 0 || terms.is_empty() {
+            terms.push(Expr::Const(constant));
+        }
+        if terms.len() == 1 { terms.pop().unwrap() } else { Expr::Xor(terms) }
+    }
+
+    fn references(&self, name: &str) -> bool {
+        match self {
+            Expr::Var(var) => var == name,
+            Expr::Const(_) => false,
+            Expr::Mod8(value) => value.references(name),
+            Expr::Shr(value, shift) => value.references(name) || shift.references(name),
+            Expr::Xor(terms) => terms.iter().any(|term| term.references(name)),
+        }
+    }
+
+    fn substitute(&self, name: &str, replacement: &Expr) -> Expr {
+        match self {
+            Expr::Var(var) if var == name => replacement.clone(),
+            Expr::Var(_) | Expr::Const(_) => self.clone(),
+            Expr::Mod8(value) => Expr::mod8(value.substitute(name, replacement)),
+            Expr::Shr(value, shift) => {
+                Expr::shr(value.substitute(name, replacement), shift.substitute(name, replacement))
+            }
+            Expr::Xor(terms) => terms.iter()
+                .map(|term| term.substitute(name, replacement))
+                .reduce(Expr::xor)
+                .unwrap_or(Expr::Const(0)),
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Var(_) | Expr::Const(_) => write!(f, "{self}"),
+            _ => write!(f, "({self})"),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Var(name) => write!(f, "{name}"),
+            Expr::Const(value) => write!(f, "{value}"),
+            Expr::Mod8(value) => {
+                value.fmt_operand(f)?;
+                write!(f, " % 8")
+            }
+            Expr::Shr(value, shift) => {
+                value.fmt_operand(f)?;
+                write!(f, " >> ")?;
+                shift.fmt_operand(f)
+            }
+            Expr::Xor(terms) => {
+                for (i, term) in terms.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ^ ")?;
+                    }
+                    term.fmt_operand(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn register_name(register: Register) -> String {
+    register.to_string().to_lowercase()
+}
+
+fn accesses(instruction: &Instruction) -> (Vec<Register>, Option<Register>) {
+    let Instruction(spec, operand) = instruction;
+    let mut reads = match operand {
+        Operand::Register(register) => vec![*register],
+        _ => Vec::new(),
+    };
+    let write = match spec.action {
+        Action::Shr { dest, src } => {
+            reads.push(src);
+            Some(dest)
+        }
+        Action::Xor { dest } => {
+            reads.push(dest);
+            Some(dest)
+        }
+        Action::XorRegister { dest, src } => {
+            reads.extend([dest, src]);
+            Some(dest)
+        }
+        Action::Mod8 { dest } | Action::Set { dest } | Action::In { dest } => Some(dest),
+        Action::Jnz { test } => {
+            reads.push(test);
+            None
+        }
+        Action::Out | Action::Halt => None,
+    };
+    (reads, write)
+}
+
+fn first_access(register: Register, instructions: &[Instruction]) -> Option<bool> {
+    instructions.iter().find_map(|instruction| {
+        let (reads, write) = accesses(instruction);
+        if reads.contains(&register) {
+            Some(true)
+        } else if write == Some(register) {
+            Some(false)
+        } else {
+            None
+        }
+    })
+}
+
+struct Region<'a> {
+    instructions: &'a [Instruction<'a>],
+    in_loop: bool,
+    suffix: &'a [Instruction<'a>],
+}
+
+impl<'a> Region<'a> {
+    fn is_live_after(&self, register: Register, position: usize) -> bool {
+        if let Some(read) = first_access(register, &self.instructions[(position + 1).min(self.instructions.len())..]) {
+            return read;
+        }
+        let read_in_loop = self.in_loop && first_access(register, self.instructions) == Some(true);
+        read_in_loop || first_access(register, self.suffix) == Some(true)
+    }
+}
+
+struct Emitter {
+    statements: Vec<String>,
+    pending: Vec<(Register, Expr)>,
+    merge: bool,
+    temporaries: usize,
+}
+
+impl Emitter {
+    fn new(merge: bool) -> Emitter {
+        Emitter {
+            statements: Vec::new(),
+            pending: Vec::new(),
+            merge,
+            temporaries: 0,
+        }
+    }
+
+    fn pending_value(&self, register: Register) -> Option<&Expr> {
+        self.pending.iter().find(|(pending, _)| *pending == register).map(|(_, value)| value)
+    }
+
+    fn read(&mut self, register: Register, target: Option<Register>) -> Expr {
+        if target != Some(register) {
+            self.flush(register, &mut Vec::new());
+        }
+        self.pending_value(register).cloned().unwrap_or_else(|| Expr::var(register))
+    }
+
+    fn read_operand(&mut self, operand: &Operand, target: Option<Register>) -> Result<Expr, String> {
+        match operand {
+            Operand::Literal(value) => Ok(Expr::Const((*value).into())),
+            Operand::Register(register) => Ok(self.read(*register, target)),
+            Operand::Ignored(_) => Ok(Expr::Const(0)),
+            Operand::Unused(value) => Err(format!("Cannot decompile reserved operand {value}")),
+        }
+    }
+
+    fn assign(&mut self, register: Register, value: Expr) {
+        self.pending.retain(|(pending, _)| *pending != register);
+        self.pending.push((register, value));
+        if !self.merge {
+            self.flush(register, &mut Vec::new());
+        }
+    }
+
+    fn flush(&mut self, register: Register, visiting: &mut Vec<Register>) {
+        let Some(value) = self.pending_value(register).cloned() else {
+            return;
+        };
+        let name = register_name(register);
+        visiting.push(register);
+        let dependents: Vec<Register> = self.pending.iter()
+            .filter(|(other, value)| *other != register && value.references(&name))
+            .map(|(other, _)| *other)
+            .collect();
+        for dependent in dependents {
+            if visiting.contains(&dependent) {
+                self.temporaries += 1;
+                let temporary = Expr::Var(format!("old_{name}{}", self.temporaries));
+                self.statements.push(format!("let {temporary} = {name}"));
+                for (_, pending) in self.pending.iter_mut() {
+                    *pending = pending.substitute(&name, &temporary);
+                }
+            } else {
+                self.flush(dependent, visiting);
+            }
+        }
+        visiting.pop();
+        let value = self.pending_value(register).cloned().unwrap_or(value);
+        self.pending.retain(|(pending, _)| *pending != register);
+        self.statements.push(Self::render_assignment(&name, &value));
+    }
+
+    fn render_assignment(name: &str, value: &Expr) -> String {
+        match value {
+            Expr::Shr(target, shift) if matches!(target.as_ref(), Expr::Var(var) if var == name) => {
+                format!("{name} >>= {shift}")
+            }
+            Expr::Xor(terms) if terms.iter().any(|term| matches!(term, Expr::Var(var) if var == name)) => {
+                let rest = terms.iter()
+                    .filter(|term| !matches!(term, Expr::Var(var) if var == name))
+                    .cloned()
+                    .reduce(Expr::xor)
+                    .unwrap_or(Expr::Const(0));
+                format!("{name} ^= {rest}")
+            }
+            _ => format!("{name} = {value}"),
+        }
+    }
+
+    fn emit_region(&mut self, region: &Region) -> Result<(), String> {
+        for (position, instruction) in region.instructions.iter().enumerate() {
+            let Instruction(spec, operand) = instruction;
+            let (reads, write) = accesses(instruction);
+            let inlined = match (spec.action, operand) {
+                (Action::Out, Operand::Register(register)) if !region.is_live_after(*register, position) => Some(*register),
+                _ => None,
+            };
+            // Materialize every other register read before inlining pending values of the target
+            for register in reads {
+                if Some(register) != write && Some(register) != inlined {
+                    self.flush(register, &mut Vec::new());
+                }
+            }
+            match spec.action {
+                Action::Shr { dest, src } => {
+                    let value = self.read(src, Some(dest));
+                    let shift = self.read_operand(operand, Some(dest))?;
+                    self.assign(dest, Expr::shr(value, shift));
+                }
+                Action::Xor { dest } => {
+                    let value = self.read(dest, Some(dest));
+                    let literal = self.read_operand(operand, Some(dest))?;
+                    self.assign(dest, Expr::xor(value, literal));
+                }
+                Action::XorRegister { dest, src } => {
+                    let value = self.read(dest, Some(dest));
+                    let other = self.read(src, Some(dest));
+                    self.assign(dest, Expr::xor(value, other));
+                }
+                Action::Mod8 { dest } => {
+                    let value = self.read_operand(operand, Some(dest))?;
+                    self.assign(dest, Expr::mod8(value));
+                }
+                Action::Set { dest } => {
+                    let value = self.read_operand(operand, Some(dest))?;
+                    self.assign(dest, value);
+                }
+                Action::Out => {
+                    let value = match inlined {
+                        Some(register) => self.read(register, Some(register)),
+                        None => self.read_operand(operand, None)?,
+                    };
+                    self.statements.push(format!("out({})", Expr::mod8(value)));
+                }
+                Action::Jnz { test } => {
+                    self.read(test, None);
+                }
+                Action::In { .. } | Action::Halt => {
+                    return Err(format!("Cannot decompile {}: input and halt instructions are not supported", spec.mnemonic));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, region: &Region) {
+        let last = region.instructions.len().saturating_sub(1);
+        let live: Vec<Register> = self.pending.iter()
+            .map(|(register, _)| *register)
+            .filter(|register| region.is_live_after(*register, last))
+            .collect();
+        for register in live {
+            self.flush(register, &mut Vec::new());
+        }
+        self.pending.clear();
+    }
+}
+
+pub fn decompile(isa: &InstructionSet, program: &[u8]) -> Result<Vec<String>, String> {
+    let instructions = isa.decode_program(program)?;
+    let mut jumps: Vec<(usize, u8, Register)> = Vec::new();
+    for (i, Instruction(spec, operand)) in instructions.iter().enumerate() {
+        match (spec.action, operand) {
+            (Action::Jnz { test }, Operand::Literal(target)) => jumps.push((i, *target, test)),
+            (Action::Jnz { .. }, _) => {
+                return Err(format!("Cannot decompile the computed jump at #{}", i * 2));
+            }
+            _ => {}
+        }
+    }
+
+    let mut lines = Vec::new();
+    match jumps.as_slice() {
+        [] => {
+            let region = Region { instructions: &instructions, in_loop: false, suffix: &[] };
+            let mut emitter = Emitter::new(true);
+            emitter.emit_region(&region)?;
+            emitter.finish(&region);
+            lines.extend(emitter.statements);
+            describe_iteration(&mut lines, &instructions, "Program")?;
+        }
+        &[(jump, target, test)] if target % 2 == 0 && usize::from(target / 2) <= jump => {
+            let head = usize::from(target / 2);
+            let body = &instructions[head..=jump];
+            let suffix = &instructions[jump + 1..];
+
+            let mut emitter = Emitter::new(true);
+            let prefix = Region { instructions: &instructions[..head], in_loop: false, suffix: &instructions[head..] };
+            emitter.emit_region(&prefix)?;
+            emitter.finish(&prefix);
+            lines.append(&mut emitter.statements);
+
+            let region = Region { instructions: body, in_loop: true, suffix };
+            emitter.emit_region(&region)?;
+            emitter.finish(&region);
+            lines.push(format!("loop {{ {} }} while {} != 0", emitter.statements.join("; "), register_name(test)));
+            emitter.statements.clear();
+
+            let region = Region { instructions: suffix, in_loop: false, suffix: &[] };
+            emitter.emit_region(&region)?;
+            emitter.finish(&region);
+            lines.extend(emitter.statements);
+            describe_iteration(&mut lines, body, "Per iteration")?;
+        }
+        _ => {
+            let targets: HashSet<usize> = jumps.iter().map(|&(_, target, _)| target.into()).collect();
+            let mut emitter = Emitter::new(false);
+            for (i, instruction) in instructions.iter().enumerate() {
+                let offset = i * 2;
+                if targets.contains(&offset) {
+                    lines.push(format!("#{offset}:"));
+                }
+                if let Instruction(OpcodeSpec { action: Action::Jnz { test }, .. }, operand) = instruction {
+                    lines.push(format!("    if {} != 0 {{ goto #{operand} }}", register_name(*test)));
+                } else {
+                    let region = Region { instructions: std::slice::from_ref(instruction), in_loop: false, suffix: &[] };
+                    emitter.emit_region(&region)?;
+                    lines.extend(emitter.statements.drain(..).map(|statement| format!("    {statement}")));
+                }
+            }
+        }
+    }
+    Ok(lines)
+}
+
+fn describe_iteration(lines: &mut Vec<String>, instructions: &[Instruction], title: &str) -> Result<(), String> {
+    let mut registers = [Register::A, Register::B, Register::C].map(|register| (register, Expr::var(register)));
+    let mut outputs = Vec::new();
+    for Instruction(spec, operand) in instructions {
+        let get = |register: Register| registers.iter().find(|(r, _)| *r == register).unwrap().1.clone();
+        let combo = || match operand {
+            Operand::Literal(value) => Ok(Expr::Const((*value).into())),
+            Operand::Register(register) => Ok(get(*register)),
+            Operand::Ignored(_) => Ok(Expr::Const(0)),
+            Operand::Unused(value) => Err(format!("Cannot decompile reserved operand {value}")),
+        };
+        let (target, value) = match spec.action {
+            Action::Shr { dest, src } => (dest, Expr::shr(get(src), combo()?)),
+            Action::Xor { dest } => (dest, Expr::xor(get(dest), combo()?)),
+            Action::XorRegister { dest, src } => (dest, Expr::xor(get(dest), get(src))),
+            Action::Mod8 { dest } => (dest, Expr::mod8(combo()?)),
+            Action::Set { dest } => (dest, combo()?),
+            Action::Out => {
+                outputs.push(Expr::mod8(combo()?));
+                continue;
+            }
+            Action::Jnz { .. } => continue,
+            Action::In { .. } | Action::Halt => {
+                return Err(format!("Cannot decompile {}: input and halt instructions are not supported", spec.mnemonic));
+            }
+        };
+        for (register, expression) in registers.iter_mut() {
+            if *register == target {
+                *expression = value.clone();
+            }
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{title}, in terms of register values at the start:"));
+    for output in outputs {
+        lines.push(format!("    out {output}"));
+    }
+    for (register, expression) in registers {
+        if expression != Expr::var(register) {
+            lines.push(format!("    {} = {expression}", register_name(register)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn decompile_standard(program: &str) -> Vec<String> {
+        decompile(&InstructionSet::standard(), &parse_program(program).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn decompiles_sample_loop() {
+        assert_eq!(decompile_standard("0,3,5,4,3,0"), [
+            "loop { a >>= 3; out(a % 8) } while a != 0",
+            "",
+            "Per iteration, in terms of register values at the start:",
+            "    out (a >> 3) % 8",
+            "    a = a >> 3",
+        ]);
+    }
+
+    #[test]
+    fn decompiles_register_dataflow() {
+        assert_eq!(decompile_standard("2,4,1,5,7,5,1,6,0,3,4,0,5,5,3,0"), [
+            "loop { b = (a % 8) ^ 5; c = a >> b; out((b ^ c ^ 6) % 8); a >>= 3 } while a != 0",
+            "",
+            "Per iteration, in terms of register values at the start:",
+            "    out ((a % 8) ^ (a >> ((a % 8) ^ 5)) ^ 3) % 8",
+            "    a = a >> 3",
+            "    b = (a % 8) ^ (a >> ((a % 8) ^ 5)) ^ 3",
+            "    c = a >> ((a % 8) ^ 5)",
+        ]);
+    }
+
+    #[test]
+    fn decompiles_straight_line_program() {
+        assert_eq!(decompile_standard("2,4,1,3,5,5"), [
+            "out(((a % 8) ^ 3) % 8)",
+            "",
+            "Program, in terms of register values at the start:",
+            "    out ((a % 8) ^ 3) % 8",
+            "    b = (a % 8) ^ 3",
+        ]);
+    }
+}
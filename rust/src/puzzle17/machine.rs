@@ -0,0 +1,444 @@
+use std::fmt;
+
+use super::{Instruction, Operand, Register};
+
+pub trait Word: Clone + PartialEq + fmt::Display + fmt::Octal + Send + Sync {
+    fn from_u64(value: u64) -> Self;
+    fn parse(text: &str) -> Result<Self, String>;
+    fn shr(&self, shift: &Self) -> Self;
+    fn xor(&self, other: &Self) -> Self;
+    fn low3(&self) -> u64;
+    fn to_usize(&self) -> Option<usize>;
+    fn is_zero(&self) -> bool;
+}
+
+impl Word for u64 {
+    fn from_u64(value: u64) -> Self {
+        value
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        text.parse().map_err(|_| format!("Invalid 64-bit value: {text}"))
+    }
+
+    fn shr(&self, shift: &Self) -> Self {
+        u32::try_from(*shift).ok().and_then(|shift| self.checked_shr(shift)).unwrap_or(0)
+    }
+
+    fn xor(&self, other: &Self) -> Self {
+        self ^ other
+    }
+
+    fn low3(&self) -> u64 {
+        self % 8
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        usize::try_from(*self).ok()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+impl Word for u128 {
+    fn from_u64(value: u64) -> Self {
+        value.into()
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        text.parse().map_err(|_| format!("Invalid 128-bit value: {text}"))
+    }
+
+    fn shr(&self, shift: &Self) -> Self {
+        u32::try_from(*shift).ok().and_then(|shift| self.checked_shr(shift)).unwrap_or(0)
+    }
+
+    fn xor(&self, other: &Self) -> Self {
+        self ^ other
+    }
+
+    fn low3(&self) -> u64 {
+        (self % 8) as u64
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        usize::try_from(*self).ok()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigWord {
+    limbs: Vec<u64>,
+}
+
+impl BigWord {
+    fn normalized(mut limbs: Vec<u64>) -> BigWord {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigWord { limbs }
+    }
+
+    fn bit(&self, index: usize) -> u32 {
+        self.limbs.get(index / 64).map_or(0, |limb| ((limb >> (index % 64)) & 1) as u32)
+    }
+}
+
+impl Word for BigWord {
+    fn from_u64(value: u64) -> Self {
+        BigWord::normalized(vec![value])
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        if text.is_empty() {
+            return Err(String::from("Invalid number: empty string"));
+        }
+        let mut limbs: Vec<u64> = Vec::new();
+        for c in text.chars() {
+            let digit = c.to_digit(10).ok_or(format!("Invalid number: {text}"))?;
+            let mut carry: u128 = digit.into();
+            for limb in limbs.iter_mut() {
+                let value = u128::from(*limb) * 10 + carry;
+                *limb = value as u64;
+                carry = value >> 64;
+            }
+            if carry != 0 {
+                limbs.push(carry as u64);
+            }
+        }
+        Ok(BigWord::normalized(limbs))
+    }
+
+    fn shr(&self, shift: &Self) -> Self {
+        let Some(shift) = shift.to_usize() else {
+            return BigWord { limbs: Vec::new() };
+        };
+        let (skip, bits) = (shift / 64, shift % 64);
+        if skip >= self.limbs.len() {
+            return BigWord { limbs: Vec::new() };
+        }
+        let limbs = (skip..self.limbs.len())
+            .map(|i| {
+                let low = self.limbs[i] >> bits;
+                let high = match self.limbs.get(i + 1) {
+                    Some(&next) if bits > 0 => next << (64 - bits),
+                    _ => 0,
+                };
+                low | high
+            })
+            .collect();
+        BigWord::normalized(limbs)
+    }
+
+    fn xor(&self, other: &Self) -> Self {
+        let len = self.limbs.len().max(other.limbs.len());
+        let limbs = (0..len)
+            .map(|i| self.limbs.get(i).copied().unwrap_or(0) ^ other.limbs.get(i).copied().unwrap_or(0))
+            .collect();
+        BigWord::normalized(limbs)
+    }
+
+    fn low3(&self) -> u64 {
+        self.limbs.first().copied().unwrap_or(0) % 8
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        match self.limbs.as_slice() {
+            [] => Some(0),
+            [value] => usize::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+}
+
+impl fmt::Display for BigWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+        let mut limbs = self.limbs.clone();
+        let mut chunks = Vec::new();
+        while !limbs.is_empty() {
+            let mut remainder: u128 = 0;
+            for limb in limbs.iter_mut().rev() {
+                let value = (remainder << 64) | u128::from(*limb);
+                *limb = (value / u128::from(CHUNK)) as u64;
+                remainder = value % u128::from(CHUNK);
+            }
+            chunks.push(remainder as u64);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{first}")?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{chunk:019}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Octal for BigWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits: String = (0..(self.limbs.len() * 64).div_ceil(3)).rev()
+            .map(|i| (0..3).fold(0, |digit, k| digit | (self.bit(i * 3 + k) << k)))
+            .skip_while(|&digit| digit == 0)
+            .map(|digit| char::from_digit(digit, 8).unwrap())
+            .collect();
+        f.pad_integral(true, "0o", if digits.is_empty() { "0" } else { &digits })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum OperandMode { Literal, Combo, Ignored }
+
+#[derive(Copy, Clone)]
+pub enum Action {
+    Shr { dest: Register, src: Register },
+    Xor { dest: Register },
+    XorRegister { dest: Register, src: Register },
+    Mod8 { dest: Register },
+    Set { dest: Register },
+    Jnz { test: Register },
+    Out,
+    In { dest: Register },
+    Halt,
+}
+
+impl Action {
+    // Disassembly text after the mnemonic, "{}" standing for the operand
+    pub fn template(&self) -> String {
+        match *self {
+            Action::Shr { dest, src } => format!("{src} >> {{}} -> {dest}"),
+            Action::Xor { dest } => format!("{dest} ^ {{}} -> {dest}"),
+            Action::XorRegister { dest, src } => format!("{dest} ^ {src} -> {dest} (with unused {{}})"),
+            Action::Mod8 { dest } => format!("{{}} % 8 -> {dest}"),
+            Action::Set { dest } => format!("{{}} -> {dest}"),
+            Action::Jnz { test } => format!("if {test} goto {{}}"),
+            Action::Out => String::from("{} % 8"),
+            Action::In { dest } => format!("input -> {dest} (with unused {{}})"),
+            Action::Halt => String::from("(with unused {})"),
+        }
+    }
+}
+
+pub struct OpcodeSpec {
+    pub mnemonic: String,
+    pub operand: OperandMode,
+    pub action: Action,
+}
+
+pub struct InstructionSet {
+    opcodes: Vec<Option<OpcodeSpec>>,
+}
+
+impl InstructionSet {
+    pub fn standard() -> InstructionSet {
+        let table = concat!(
+            "0 adv combo shr A A\n",
+            "1 bxl literal xor B\n",
+            "2 bst combo mod8 B\n",
+            "3 jnz literal jnz A\n",
+            "4 bxc ignored xorreg B C\n",
+            "5 out combo out\n",
+            "6 bdv combo shr B A\n",
+            "7 cdv combo shr C A\n",
+        );
+        InstructionSet::parse(table).unwrap()
+    }
+
+    pub fn parse(table: &str) -> Result<InstructionSet, String> {
+        let mut isa = InstructionSet { opcodes: Vec::new() };
+        for (i, line) in table.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (code, spec) = Self::parse_entry(line)
+                .map_err(|err| format!("Instruction set line {}: {err}", i + 1))?;
+            isa.define(code, spec)
+                .map_err(|err| format!("Instruction set line {}: {err}", i + 1))?;
+        }
+        Ok(isa)
+    }
+
+    pub fn define(&mut self, code: u8, spec: OpcodeSpec) -> Result<(), String> {
+        let index = usize::from(code);
+        if self.opcodes.len() <= index {
+            self.opcodes.resize_with(index + 1, || None);
+        }
+        if let Some(existing) = &self.opcodes[index] {
+            return Err(format!("Opcode {code} is already defined as {}", existing.mnemonic));
+        }
+        self.opcodes[index] = Some(spec);
+        Ok(())
+    }
+
+    pub fn get(&self, code: u8) -> Option<&OpcodeSpec> {
+        self.opcodes.get(usize::from(code)).and_then(|spec| spec.as_ref())
+    }
+
+    pub fn find(&self, mnemonic: &str) -> Option<(u8, &OpcodeSpec)> {
+        self.opcodes.iter()
+            .enumerate()
+            .find_map(|(code, spec)| spec.as_ref()
+                .filter(|spec| spec.mnemonic.eq_ignore_ascii_case(mnemonic))
+                .map(|spec| (code as u8, spec))
+            )
+    }
+
+    pub fn decode(&self, code: u8, operand: u8) -> Option<Instruction<'_>> {
+        let spec = self.get(code)?;
+        let operand = match spec.operand {
+            OperandMode::Literal => Operand::Literal(operand),
+            OperandMode::Combo => match operand {
+                0..=3 => Operand::Literal(operand),
+                4 => Operand::Register(Register::A),
+                5 => Operand::Register(Register::B),
+                6 => Operand::Register(Register::C),
+                _ => Operand::Unused(operand),
+            },
+            OperandMode::Ignored => Operand::Ignored(operand),
+        };
+        Some(Instruction(spec, operand))
+    }
+
+    pub fn decode_program(&self, program: &[u8]) -> Result<Vec<Instruction<'_>>, String> {
+        let chunks = program.chunks_exact(2);
+        if !chunks.remainder().is_empty() {
+            return Err(String::from("Unexpected trailing program bytes"));
+        }
+        chunks
+            .enumerate()
+            .map(|(i, pair)| self.decode(pair[0], pair[1])
+                .ok_or(format!("Invalid instruction #{i}: {},{}", pair[0], pair[1]))
+            )
+            .collect()
+    }
+
+    fn parse_entry(line: &str) -> Result<(u8, OpcodeSpec), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let [code, mnemonic, operand, action, args @ ..] = words.as_slice() else {
+            return Err(format!("Expected '<code> <mnemonic> <literal|combo|ignored> <action> [registers]': {line}"));
+        };
+        let code = code.parse::<u8>().map_err(|_| format!("Invalid opcode: {code}"))?;
+        let operand = match *operand {
+            "literal" => OperandMode::Literal,
+            "combo" => OperandMode::Combo,
+            "ignored" => OperandMode::Ignored,
+            _ => {
+                return Err(format!("Invalid operand mode: {operand}"));
+            }
+        };
+        let registers = args.iter().map(|arg| parse_register(arg)).collect::<Result<Vec<_>, _>>()?;
+        let action = match (*action, registers.as_slice()) {
+            ("shr", &[dest, src]) => Action::Shr { dest, src },
+            ("xor", &[dest]) => Action::Xor { dest },
+            ("xorreg", &[dest, src]) => Action::XorRegister { dest, src },
+            ("mod8", &[dest]) => Action::Mod8 { dest },
+            ("set", &[dest]) => Action::Set { dest },
+            ("jnz", &[test]) => Action::Jnz { test },
+            ("out", &[]) => Action::Out,
+            ("in", &[dest]) => Action::In { dest },
+            ("halt", &[]) => Action::Halt,
+            _ => {
+                return Err(format!("Invalid action '{}' with {} register(s)", action, registers.len()));
+            }
+        };
+        Ok((code, OpcodeSpec { mnemonic: mnemonic.to_string(), operand, action }))
+    }
+}
+
+pub(crate) fn parse_register(value: &str) -> Result<Register, String> {
+    match value {
+        "A" | "a" => Ok(Register::A),
+        "B" | "b" => Ok(Register::B),
+        "C" | "c" => Ok(Register::C),
+        _ => Err(format!("Invalid register: {value}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComputerDescription, ComputerState};
+
+    fn run_description<W: Word>(description: &ComputerDescription<W>) -> Result<(Vec<u64>, String), String> {
+        let mut state = ComputerState::new();
+        let mut output = Vec::new();
+        description.initialize(&mut state);
+        description.run(&mut state, Some(100_000), |n| output.push(n))?;
+        let registers = state.registers.iter().map(|register| register.to_string()).collect::<Vec<_>>().join(",");
+        Ok((output, registers))
+    }
+
+    fn run_word<W: Word>(a: &str, program: &str) -> Result<(Vec<u64>, String), String> {
+        let content = format!("Register A: {a}\nRegister B: 0\nRegister C: 0\n\nProgram: {program}\n");
+        run_description(&ComputerDescription::<W>::parse(&content)?)
+    }
+
+    #[test]
+    fn runs_the_same_under_every_width() {
+        let cases = [
+            ("729", "0,1,5,4,3,0"),
+            ("117440", "0,3,5,4,3,0"),
+            ("66752888", "2,4,1,5,7,5,1,6,0,3,4,0,5,5,3,0"),
+            ("18446744073709551615", "2,4,1,5,7,5,1,6,0,3,4,0,5,5,3,0"),
+        ];
+        for (a, program) in cases {
+            let expected = run_word::<u64>(a, program).unwrap();
+            assert_eq!(run_word::<u128>(a, program).unwrap(), expected, "register A {a}");
+            assert_eq!(run_word::<BigWord>(a, program).unwrap(), expected, "register A {a}");
+        }
+    }
+
+    #[test]
+    fn runs_values_wider_than_64_bits() {
+        // 2^100 + 5, shifted right three bits per output
+        let a = "1267650600228229401496703205381";
+        assert!(run_word::<u64>(a, "0,3,5,4,3,0").is_err());
+        let expected = run_word::<u128>(a, "0,3,5,4,3,0").unwrap();
+        assert_eq!(expected.0.len(), 34);
+        assert_eq!(run_word::<BigWord>(a, "0,3,5,4,3,0").unwrap(), expected);
+    }
+
+    #[test]
+    fn computes_with_big_words() {
+        let two_130 = BigWord::parse("1361129467683753853853498429727072845824").unwrap();
+        assert_eq!(two_130.shr(&BigWord::from_u64(127)), BigWord::from_u64(8));
+        assert_eq!(two_130.shr(&BigWord::from_u64(131)), BigWord::from_u64(0));
+        assert_eq!(two_130.xor(&two_130), BigWord::from_u64(0));
+        assert_eq!(two_130.xor(&BigWord::from_u64(7)).low3(), 7);
+        assert_eq!(two_130.to_usize(), None);
+        assert_eq!(two_130.to_string(), "1361129467683753853853498429727072845824");
+        assert_eq!(format!("{two_130:o}"), format!("2{}", "0".repeat(43)));
+        assert_eq!(format!("{:#o}", BigWord::from_u64(0)), "0o0");
+    }
+
+    #[test]
+    fn reads_input_instruction() {
+        let table = "0 adv combo shr A A\n5 out combo out\n8 inp ignored in A\n9 hlt ignored halt\n";
+        let isa = InstructionSet::parse(table).unwrap();
+        let content = "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: 8,0,5,4,8,0,0,1,5,4,9,0,5,4\n";
+        let description = ComputerDescription::<u64>::parse(content).unwrap().with_isa(isa);
+
+        let with_input = description.with_input(vec![3, 13]);
+        assert_eq!(run_description(&with_input), Ok((vec![3, 6], String::from("6,0,0"))));
+
+        let description = with_input.with_input(vec![3]);
+        assert_eq!(run_description(&description), Err(String::from("Input queue is empty at #4")));
+    }
+}
@@ -1,52 +1,141 @@
-use core::get_data_path;
-use fplist::{PersistentList, cons};
+use core::{get_data_path, split_options};
+use machine::{Action, InstructionSet, OpcodeSpec, Word};
 use regex::Regex;
-use std::{env, iter};
-use std::collections::{HashMap, HashSet};
+use std::env;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fs::{File, read_to_string};
-use std::io::{stdout, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::io::{stdin, stdout, BufReader, BufWriter, LineWriter, Write};
+use std::thread;
+
+mod assembler;
+mod compiled;
+mod debugger;
+mod decompiler;
+mod machine;
+mod sat;
+mod symbolic;
 
 fn main() {
-    if env::args().nth(1).is_some_and(|arg| arg == "--disassemble") {
-        if let Some(program) = env::args().nth(2) {
-            match ComputerDescription::parse_program(&program) {
-                Ok(parsed) => {
-                    if let Err(err) = disassemble(&mut stdout(), &parsed) {
-                        eprintln!("Error disassembling the program:\n{}", err);
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("--disassemble") => {
+            let (positional, options) = split_options(&args[2..], &["--isa"]);
+            if let Some(program) = positional.first() {
+                match load_isa(&options).and_then(|isa| Ok((parse_program(program)?, isa))) {
+                    Ok((parsed, isa)) => {
+                        if let Err(err) = disassemble(&mut stdout(), &isa, &parsed) {
+                            eprintln!("Error disassembling the program:\n{}", err);
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("Error parsing the program:\n{}", err)
                     }
                 }
-                Err(err) => {
-                    eprintln!("Error parsing the program:\n{}", err)
+            } else {
+                eprintln!("Missing program to disassemble");
+            }
+        }
+        Some("--decompile") => {
+            let (positional, options) = split_options(&args[2..], &["--isa"]);
+            if let Some(program) = positional.first() {
+                let result = load_isa(&options)
+                    .and_then(|isa| decompiler::decompile(&isa, &parse_program(program)?));
+                match result {
+                    Ok(lines) => {
+                        for line in lines {
+                            println!("{}", line);
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("Error decompiling the program:\n{}", err)
+                    }
                 }
+            } else {
+                eprintln!("Missing program to decompile");
             }
-        } else {
-            eprintln!("Missing program to disassemble");
         }
-    } else {
-        use std::time::Instant;
-        let before = Instant::now();
-        basic();
-        advanced();
-        println!("Elapsed time: {:.2?}", before.elapsed());
+        Some("--assemble") => {
+            let (positional, options) = split_options(&args[2..], &["--isa"]);
+            if let Some(path) = positional.first() {
+                let result = read_to_string(path)
+                    .map_err(|err| format!("Failed to read {path}: {err}"))
+                    .and_then(|source| assembler::assemble(&load_isa(&options)?, &source));
+                match result {
+                    Ok(program) => {
+                        let joined = program.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(",");
+                        println!("{}", joined);
+                    }
+                    Err(err) => {
+                        eprintln!("Error assembling the program:\n{}", err)
+                    }
+                }
+            } else {
+                eprintln!("Missing file to assemble");
+            }
+        }
+        Some("--solve") => {
+            let (positional, options) = split_options(&args[2..], &["--isa", "--check"]);
+            let solver: Box<dyn symbolic::PathSolver> = if options.contains_key("--bit-blast") {
+                Box::new(symbolic::BitBlastSolver)
+            } else if options.contains_key("--int-solver") {
+                Box::new(symbolic::IntPathSolver { solver: core::default_int_solver() })
+            } else {
+                symbolic::default_path_solver()
+            };
+            let check = options.get("--check")
+                .map(|value| value.parse::<u64>().map_err(|_| format!("Invalid check limit: {value}")))
+                .transpose();
+            if let Some(program) = positional.first() {
+                let result = check.and_then(|check| {
+                    let isa = load_isa(&options)?;
+                    solve(&isa, program, positional.get(1).copied(), solver.as_ref(), check)
+                });
+                if let Err(err) = result {
+                    eprintln!("Error solving for register A:\n{}", err);
+                }
+            } else {
+                eprintln!("Missing program to solve for");
+            }
+        }
+        Some(mode @ ("--machine" | "--debug" | "--trace")) => {
+            let (positional, options) = split_options(
+                &args[2..],
+                &["--isa", "--word", "--input", "--script", "--out", "--max-cycles"],
+            );
+            let input_path = positional.first()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| get_data_path("input/puzzle17.txt"));
+            if let Err(err) = run_computer(mode, &input_path, &options) {
+                eprintln!("Error running the program:\n{}", err);
+            }
+        }
+        _ => {
+            use std::time::Instant;
+            let before = Instant::now();
+            basic();
+            advanced();
+            println!("Elapsed time: {:.2?}", before.elapsed());
+        }
     }
 }
 
 fn basic() {
     let input = read_to_string(get_data_path("input/puzzle17.txt")).unwrap();
-    let description = ComputerDescription::parse(&input).unwrap();
+    let description: ComputerDescription = ComputerDescription::parse(&input).unwrap();
 
     {
         let mut assembly_writer = LineWriter::new(
             File::create(get_data_path("output/puzzle17_program.txt")).unwrap()
         );
-        disassemble(&mut assembly_writer, &description.program).unwrap();
+        disassemble(&mut assembly_writer, &description.isa, &description.program).unwrap();
     }
 
     let mut state = ComputerState::new();
     let mut output = Vec::new();
     description.initialize(&mut state);
-    description.run(&mut state, |n| output.push(n)).unwrap();
+    description.run(&mut state, None, |n| output.push(n)).unwrap();
 
     let joined_output = output.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(",");
     println!("Program output (basic): {}", joined_output);
@@ -54,100 +143,176 @@ fn basic() {
 
 fn advanced() {
     let input = read_to_string(get_data_path("input/puzzle17.txt")).unwrap();
-    let description = ComputerDescription::parse(&input).unwrap();
+    let description: ComputerDescription = ComputerDescription::parse(&input).unwrap();
+
+    let solver = symbolic::default_path_solver();
+    let min_result = symbolic::find_min_input(
+        &description.isa,
+        &description.program,
+        *description.register(Register::B),
+        *description.register(Register::C),
+        &description.program,
+        solver.as_ref(),
+    ).unwrap().expect("No register A value makes the program output itself");
+
+    let mut state = ComputerState::new();
+    let mut output = Vec::new();
+    description.initialize(&mut state);
+    state.registers[Register::A as usize] = min_result;
+    description.run(&mut state, None, |n| output.push(n)).unwrap();
+    let joined_output = output.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(",");
 
-    let mut linear_program = description.program.clone();
-    linear_program.splice((description.program.len() - 2).., iter::empty());
-    let linear = ComputerDescription {
-        program: linear_program,
-        ..description
+    println!("Min register A for quine (advanced): {} with output {}", min_result, joined_output);
+}
+
+const VERIFY_CYCLE_LIMIT: u64 = 10_000_000;
+const CHECK_CHUNK_SIZE: u64 = 1 << 16;
+
+fn solve(
+    isa: &InstructionSet,
+    program: &str,
+    expected_output: Option<&str>,
+    solver: &dyn symbolic::PathSolver,
+    check: Option<u64>,
+) -> Result<(), String> {
+    let program = parse_program(program)?;
+    let expected = match expected_output {
+        Some(output) => parse_program(output)?,
+        None => program.clone(),
     };
 
-    let mut out_to_a: HashMap<u8, HashSet<u64>> = HashMap::new();
-    for a_bits in 0..1024 {
-        let mut state = ComputerState {
-            register_a: a_bits,
-            register_b: 0,
-            register_c: 0,
-            instruction: 0,
-        };
-        let mut out: u8 = 0;
-        linear.run(&mut state, |n| out = n.try_into().unwrap()).unwrap();
-        if let Some(a_variants) = out_to_a.get_mut(&out) {
-            a_variants.insert(a_bits);
-        } else {
-            out_to_a.insert(out, [a_bits].into());
+    let found = symbolic::find_min_input(isa, &program, 0, 0, &expected, solver)?;
+    let compiled = compiled::CompiledProgram::compile(isa, &program).with_cycle_limit(VERIFY_CYCLE_LIMIT);
+    match found {
+        Some(register_a) => {
+            let mut output = Vec::new();
+            compiled.run([register_a, 0, 0], &mut output)?;
+            let joined_output = output.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(",");
+            println!("Min register A: {} with output {}", register_a, joined_output);
+        }
+        None => {
+            println!("No register A value produces the requested output");
         }
     }
 
-    // let mut log_writer = LineWriter::new(
-    //     File::create(get_data_path("output/puzzle17_log.txt")).unwrap()
-    // );
-
-    let mut stack: Vec<(usize, PersistentList<u64>)> = Vec::new();
-    stack.push((0, PersistentList::new()));
-    let mut results = HashSet::new();
-
-    while let Some((next_step, list)) = stack.pop() {
-        if next_step >= description.program.len() + 3 {
-            let mut result = 0;
-            for byte in list {
-                result = (result << 3) | (byte & 0b111);
+    // Cross-check the solver against running every register A value below the limit, in
+    // ascending chunks so that the search stops at the first chunk with a match
+    if let Some(limit) = check {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let brute_force = (0..limit).step_by(CHECK_CHUNK_SIZE as usize).find_map(|start| {
+            let a_values: Vec<u64> = (start..limit.min(start + CHECK_CHUNK_SIZE)).collect();
+            a_values.iter()
+                .zip(compiled.run_batch(&a_values, 0, 0, threads))
+                .find(|(_, output)| output.as_ref().is_ok_and(|output| *output == expected))
+                .map(|(&register_a, _)| register_a)
+        });
+        match (brute_force, found) {
+            (Some(register_a), Some(solved)) if register_a != solved => {
+                return Err(format!("Running every value finds register A {register_a}, the solver found {solved}"));
             }
-            results.insert(result);
-        } else {
-            let from_out = description.program.get(next_step).and_then(|next_byte| out_to_a.get(next_byte));
-            let mut candidates: Vec<u64> = Vec::new();
-            if let Some(from_out) = from_out {
-                candidates.extend(from_out.iter());
-            } else {
-                candidates.extend(0..1024);
+            (Some(register_a), None) => {
+                return Err(format!("Running every value finds register A {register_a}, the solver found none"));
             }
+            (Some(_), _) => println!("Running every value below {limit} agrees"),
+            (None, _) => println!("No register A value below {limit} produces the requested output"),
+        }
+    }
+    Ok(())
+}
 
-            'candidate: for candidate in candidates {
-                let mut current = list.clone();
-                let mut mask = 0b1111111;
-                for i in 1..=3 {
-                    if let Some(expected) = current.first().clone() {
-                        let diff = (expected >> (i * 3)) ^ (candidate & mask);
-                        if diff != 0 {
-                            continue 'candidate;
-                        }
-                        current = current.rest();
-                        mask >>= 3;
-                    } else {
-                        break;
-                    }
-                }
-                let next_list = cons(candidate, list.clone());
-                // writeln!(log_writer, "{}", next_list).unwrap();
-                stack.push((next_step + 1, next_list));
-            }
+fn load_isa(options: &HashMap<&str, &str>) -> Result<InstructionSet, String> {
+    match options.get("--isa") {
+        Some(path) => {
+            let table = read_to_string(path).map_err(|err| format!("Failed to read {path}: {err}"))?;
+            InstructionSet::parse(&table)
+        }
+        None => Ok(InstructionSet::standard()),
+    }
+}
+
+fn run_computer(mode: &str, input_path: &Path, options: &HashMap<&str, &str>) -> Result<(), String> {
+    match options.get("--word").copied().unwrap_or("u64") {
+        "u64" => run_computer_with::<u64>(mode, input_path, options),
+        "u128" => run_computer_with::<u128>(mode, input_path, options),
+        "big" => run_computer_with::<machine::BigWord>(mode, input_path, options),
+        word => Err(format!("Unknown word type: {word} (expected u64, u128 or big)")),
+    }
+}
+
+fn run_computer_with<W: Word>(mode: &str, input_path: &Path, options: &HashMap<&str, &str>) -> Result<(), String> {
+    let content = read_to_string(input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))?;
+    let input = options.get("--input")
+        .into_iter()
+        .flat_map(|input| input.split(','))
+        .map(|value| W::parse(value.trim()))
+        .collect::<Result<Vec<W>, String>>()?;
+    let description = ComputerDescription::<W>::parse(&content)?
+        .with_isa(load_isa(options)?)
+        .with_input(input);
+    let max_cycles = options.get("--max-cycles")
+        .map(|value| value.parse::<u64>().map_err(|_| format!("Invalid cycle limit: {value}")))
+        .transpose()?;
+    match mode {
+        "--debug" => debug(&description, options.get("--script").copied(), max_cycles),
+        "--trace" => trace(&description, options.get("--out").copied(), max_cycles),
+        _ => run_machine(&description, max_cycles),
+    }
+}
+
+fn debug<W: Word>(description: &ComputerDescription<W>, script: Option<&str>, max_cycles: Option<u64>) -> Result<(), String> {
+    let mut debugger = debugger::Debugger::new(description, max_cycles);
+    println!("{}", debugger.describe());
+    match script {
+        Some(script) => {
+            let file = File::open(script).map_err(|err| format!("Failed to open {script}: {err}"))?;
+            debugger.run_commands(BufReader::new(file), &mut stdout(), true)
         }
+        None => debugger.run_commands(stdin().lock(), &mut stdout(), false),
     }
+}
 
-    let min_result = results.iter().min().unwrap();
+fn trace<W: Word>(description: &ComputerDescription<W>, out: Option<&str>, max_cycles: Option<u64>) -> Result<(), String> {
+    let output = match out {
+        Some(path) => {
+            let file = File::create(path).map_err(|err| format!("Failed to create {path}: {err}"))?;
+            debugger::trace_program(description, BufWriter::new(file), max_cycles)?
+        }
+        None => debugger::trace_program(description, stdout().lock(), max_cycles)?,
+    };
+    let joined_output = output.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(",");
+    println!("Program output: {}", joined_output);
+    Ok(())
+}
+
+fn run_machine<W: Word>(description: &ComputerDescription<W>, max_cycles: Option<u64>) -> Result<(), String> {
     let mut state = ComputerState::new();
     let mut output = Vec::new();
     description.initialize(&mut state);
-    state.register_a = *min_result;
-    description.run(&mut state, |n| output.push(n)).unwrap();
+    description.run(&mut state, max_cycles, |n| output.push(n))?;
     let joined_output = output.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(",");
-
-    println!("Min register A for quine (advanced): {} with output {}", min_result, joined_output);
+    println!("Program output: {}", joined_output);
+    println!(
+        "Registers: A={} B={} C={}",
+        state.get(Register::A),
+        state.get(Register::B),
+        state.get(Register::C)
+    );
+    Ok(())
 }
 
-fn disassemble<W: Write>(writer: &mut W, program: &Vec<u8>) -> Result<(), Box<dyn Error>> {
-    let instructions: Vec<_> = ComputerDescription::decode_program(program)?;
+fn disassemble<W: Write>(writer: &mut W, isa: &InstructionSet, program: &[u8]) -> Result<(), Box<dyn Error>> {
+    let instructions: Vec<_> = isa.decode_program(program)?;
     let mut labels: HashSet<usize> = HashSet::new();
     for instruction in instructions.iter() {
-        if let Instruction(Opcode::Jnz, Operand::Literal(label)) = instruction {
+        if let Instruction(OpcodeSpec { action: Action::Jnz { .. }, .. }, Operand::Literal(label)) = instruction {
             labels.insert((*label).into());
         }
     }
     for (i, instruction) in instructions.iter().enumerate() {
-        if labels.contains(&i) {
-            writeln!(writer, "#{}: {}", i, instruction)?;
+        let offset = i * 2;
+        if labels.contains(&offset) {
+            writeln!(writer, "#{}: {}", offset, instruction)?;
         } else {
             writeln!(writer, "    {}", instruction)?;
         }
@@ -155,15 +320,24 @@ fn disassemble<W: Write>(writer: &mut W, program: &Vec<u8>) -> Result<(), Box<dy
     return Ok(());
 }
 
-struct ComputerDescription {
-    register_a: u64,
-    register_b: u64,
-    register_c: u64,
+fn parse_program(program: &str) -> Result<Vec<u8>, String> {
+    program.split(",")
+        .enumerate()
+        .map(|(i, v)| v.parse::<u8>()
+            .map_err(|_| format!("Invalid instruction byte #{i}: {v}"))
+        )
+        .collect()
+}
+
+struct ComputerDescription<W: Word = u64> {
+    registers: [W; 3],
     program: Vec<u8>,
+    isa: InstructionSet,
+    input: Vec<W>,
 }
 
-impl ComputerDescription {
-    fn parse(content: &str) -> Result<ComputerDescription, String> {
+impl<W: Word> ComputerDescription<W> {
+    fn parse(content: &str) -> Result<ComputerDescription<W>, String> {
         let re = Regex::new(concat!(
             r"^Register A: (\d+)\r?\n",
             r"Register B: (\d+)\r?\n",
@@ -177,212 +351,184 @@ impl ComputerDescription {
             .map(|c| c.extract())
             .ok_or(format!("Failed to parse computer: {}", content))?;
 
-        let register_a = register_a.parse().map_err(|_| format!("Failed to parse register A: {}", register_a))?;
-        let register_b = register_b.parse().map_err(|_| format!("Failed to parse register B: {}", register_b))?;
-        let register_c = register_c.parse().map_err(|_| format!("Failed to parse register C: {}", register_c))?;
-        let program = Self::parse_program(program)?;
+        let register_a = W::parse(register_a).map_err(|_| format!("Failed to parse register A: {}", register_a))?;
+        let register_b = W::parse(register_b).map_err(|_| format!("Failed to parse register B: {}", register_b))?;
+        let register_c = W::parse(register_c).map_err(|_| format!("Failed to parse register C: {}", register_c))?;
+        let program = parse_program(program)?;
+
+        return Ok(ComputerDescription {
+            registers: [register_a, register_b, register_c],
+            program,
+            isa: InstructionSet::standard(),
+            input: Vec::new(),
+        });
+    }
 
-        return Ok(ComputerDescription { register_a, register_b, register_c, program });
+    fn with_isa(mut self, isa: InstructionSet) -> ComputerDescription<W> {
+        self.isa = isa;
+        self
     }
 
-    fn parse_program(program: &str) -> Result<Vec<u8>, String> {
-        program.split(",")
-            .enumerate()
-            .map(|(i, v)| v.parse::<u8>()
-                .map_err(|_| format!("Invalid instruction byte #{i}: {v}"))
-            )
-            .collect()
+    fn with_input(mut self, input: Vec<W>) -> ComputerDescription<W> {
+        self.input = input;
+        self
     }
 
-    fn decode_program(program: &Vec<u8>) -> Result<Vec<Instruction>, String> {
-        let chunks = program.chunks_exact(2);
-        let decoded = chunks
-            .clone()
-            .enumerate()
-            .map(|(i, pair)| Instruction::decode(pair[0], pair[1])
-                .ok_or(format!("Invalid instruction #{i}: {},{}", pair[0], pair[1]))
-            )
-            .collect();
-        if chunks.remainder().len() > 0 {
-            return Err(format!("Unexpected trailing program bytes"));
-        }
-        return decoded;
+    fn register(&self, register: Register) -> &W {
+        &self.registers[register as usize]
     }
 
-    fn initialize(&self, state: &mut ComputerState) {
-        state.register_a = self.register_a;
-        state.register_b = self.register_b;
-        state.register_c = self.register_c;
+    fn instruction_at(&self, index: usize) -> Option<Instruction<'_>> {
+        let raw_code = *self.program.get(index)?;
+        let raw_operand = *self.program.get(index + 1)?;
+        self.isa.decode(raw_code, raw_operand)
+    }
+
+    fn initialize(&self, state: &mut ComputerState<W>) {
+        state.registers = self.registers.clone();
         state.instruction = 0;
+        state.input = self.input.iter().cloned().collect();
     }
 
-    fn run(&self, state: &mut ComputerState, mut handle_output: impl FnMut(u64) -> ()) -> Result<(), String> {
-        while state.instruction < self.program.len() {
-            let index: usize = state.instruction;
-            let raw_code = self.program[index];
-            let raw_operand = self.program[index + 1];
-            let Instruction(opcode, operand) = Instruction::decode(raw_code, raw_operand)
-                .ok_or(format!("Invalid instruction #{index}: {raw_code},{raw_operand}"))?;
-            match opcode {
-                Opcode::Adv => {
-                    state.register_a >>= state.read(&operand)?;
-                }
-                Opcode::Bxl => {
-                    state.register_b ^= state.read(&operand)?;
-                }
-                Opcode::Bst => {
-                    state.register_b = state.read(&operand)? % 8;
+    fn run(
+        &self,
+        state: &mut ComputerState<W>,
+        max_cycles: Option<u64>,
+        mut handle_output: impl FnMut(u64),
+    ) -> Result<(), String> {
+        let mut cycles: u64 = 0;
+        while self.step(state, &mut handle_output)? {
+            cycles += 1;
+            if let Some(max_cycles) = max_cycles.filter(|&max_cycles| cycles >= max_cycles) {
+                if state.instruction < self.program.len() {
+                    return Err(format!("Program did not halt within {max_cycles} cycles"));
                 }
-                Opcode::Jnz => {
-                    if state.register_a != 0 {
-                        state.instruction = state.read(&operand)?
-                            .try_into()
-                            .map_err(|_| "Cannot convert value to pointer")?;
-                        continue;
-                    }
-                }
-                Opcode::Bxc => {
-                    state.register_b ^= state.register_c;
-                }
-                Opcode::Out => {
-                    handle_output(state.read(&operand)? % 8);
-                }
-                Opcode::Bdv => {
-                    state.register_b = state.register_a >> state.read(&operand)?;
-                }
-                Opcode::Cdv => {
-                    state.register_c = state.register_a >> state.read(&operand)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn step(&self, state: &mut ComputerState<W>, handle_output: &mut impl FnMut(u64)) -> Result<bool, String> {
+        if state.instruction >= self.program.len() {
+            return Ok(false);
+        }
+        let index: usize = state.instruction;
+        let raw_code = self.program[index];
+        let raw_operand = *self.program.get(index + 1)
+            .ok_or(format!("Missing operand for instruction #{index}"))?;
+        let Instruction(spec, operand) = self.isa.decode(raw_code, raw_operand)
+            .ok_or(format!("Invalid instruction #{index}: {raw_code},{raw_operand}"))?;
+        let value = state.read(&operand)?;
+        let registers = &mut state.registers;
+        match spec.action {
+            Action::Shr { dest, src } => {
+                registers[dest as usize] = registers[src as usize].shr(&value);
+            }
+            Action::Xor { dest } => {
+                registers[dest as usize] = registers[dest as usize].xor(&value);
+            }
+            Action::XorRegister { dest, src } => {
+                registers[dest as usize] = registers[dest as usize].xor(&registers[src as usize]);
+            }
+            Action::Mod8 { dest } => {
+                registers[dest as usize] = W::from_u64(value.low3());
+            }
+            Action::Set { dest } => {
+                registers[dest as usize] = value;
+            }
+            Action::Jnz { test } => {
+                if !registers[test as usize].is_zero() {
+                    state.instruction = value.to_usize()
+                        .ok_or(format!("Jump target {value} out of range at #{index}"))?;
+                    return Ok(true);
                 }
             }
-            state.instruction += 2;
+            Action::Out => {
+                handle_output(value.low3());
+            }
+            Action::In { dest } => {
+                registers[dest as usize] = state.input.pop_front()
+                    .ok_or(format!("Input queue is empty at #{index}"))?;
+            }
+            Action::Halt => {
+                state.instruction = self.program.len();
+                return Ok(true);
+            }
         }
-        return Ok(());
+        state.instruction += 2;
+        Ok(true)
     }
 }
 
-struct ComputerState {
-    register_a: u64,
-    register_b: u64,
-    register_c: u64,
+struct ComputerState<W: Word = u64> {
+    registers: [W; 3],
     instruction: usize,
+    input: VecDeque<W>,
 }
 
-impl ComputerState {
-    fn new() -> ComputerState {
+impl<W: Word> ComputerState<W> {
+    fn new() -> ComputerState<W> {
         ComputerState {
-            register_a: 0,
-            register_b: 0,
-            register_c: 0,
+            registers: [W::from_u64(0), W::from_u64(0), W::from_u64(0)],
             instruction: 0,
+            input: VecDeque::new(),
         }
     }
 
-    fn read(&self, operand: &Operand) -> Result<u64, String> {
+    fn read(&self, operand: &Operand) -> Result<W, String> {
         match operand {
-            Operand::Literal(value) => Ok((*value).into()),
-            Operand::Register(Register::A) => Ok(self.register_a),
-            Operand::Register(Register::B) => Ok(self.register_b),
-            Operand::Register(Register::C) => Ok(self.register_c),
+            Operand::Literal(value) => Ok(W::from_u64((*value).into())),
+            Operand::Register(register) => Ok(self.get(*register).clone()),
+            Operand::Ignored(_) => Ok(W::from_u64(0)),
             Operand::Unused(value) => Err(format!("Cannot read unsed operand with value {value}"))
         }
     }
-}
 
-struct Instruction(Opcode, Operand);
-
-enum Opcode {
-    Adv = 0,
-    Bxl = 1,
-    Bst = 2,
-    Jnz = 3,
-    Bxc = 4,
-    Out = 5,
-    Bdv = 6,
-    Cdv = 7,
+    fn get(&self, register: Register) -> &W {
+        &self.registers[register as usize]
+    }
 }
 
+// An instruction decoded through the instruction set table
+struct Instruction<'a>(&'a OpcodeSpec, Operand);
+
+#[derive(Copy, Clone)]
 enum Operand {
     Literal(u8),
     Register(Register),
+    Ignored(u8),
     Unused(u8),
 }
 
+#[derive(Copy, Clone, PartialEq)]
 enum Register { A, B, C }
 
-impl Instruction {
-    fn decode(opcode: u8, operand: u8) -> Option<Instruction> {
-        match opcode {
-            0 => Some(Instruction(Opcode::Adv, Self::decode_combo(operand))),
-            1 => Some(Instruction(Opcode::Bxl, Self::decode_literal(operand))),
-            2 => Some(Instruction(Opcode::Bst, Self::decode_combo(operand))),
-            3 => Some(Instruction(Opcode::Jnz, Self::decode_literal(operand))),
-            4 => Some(Instruction(Opcode::Bxc, Self::decode_literal(operand))),
-            5 => Some(Instruction(Opcode::Out, Self::decode_combo(operand))),
-            6 => Some(Instruction(Opcode::Bdv, Self::decode_combo(operand))),
-            7 => Some(Instruction(Opcode::Cdv, Self::decode_combo(operand))),
-            _ => None,
-        }
-    }
-
-    fn decode_literal(operand: u8) -> Operand {
-        Operand::Literal(operand)
-    }
-
-    fn decode_combo(operand: u8) -> Operand {
-        match operand {
-            0..=3 => Operand::Literal(operand),
-            4 => Operand::Register(Register::A),
-            5 => Operand::Register(Register::B),
-            6 => Operand::Register(Register::C),
-            _ => Operand::Unused(operand),
-        }
+impl std::fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operand = match (self.0.action, self.1) {
+            (Action::Jnz { .. }, Operand::Literal(value)) => format!("#{value}"),
+            (_, operand) => operand.to_string(),
+        };
+        write!(f, "{} {}", self.0.mnemonic, self.0.action.template().replace("{}", &operand))
     }
 }
 
-impl std::fmt::Display for Instruction {
+impl std::fmt::Display for Operand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.0 {
-            Opcode::Adv => {
-                write!(f, "adv A >> {} -> A", self.1)
-            }
-            Opcode::Bxl => {
-                write!(f, "bxl B ^ {} -> B", self.1)
-            }
-            Opcode::Bst => {
-                write!(f, "bst {} % 8 -> B", self.1)
-            }
-            Opcode::Jnz => {
-                write!(f, "jnz if A goto #{}", self.1)
-            }
-            Opcode::Bxc => {
-                write!(f, "bxc B ^ C -> B (with unused {})", self.1)
-            }
-            Opcode::Out => {
-                write!(f, "out {} % 8", self.1)
-            }
-            Opcode::Bdv => {
-                write!(f, "bdv A >> {} -> B", self.1)
-            }
-            Opcode::Cdv => {
-                write!(f, "cdv A >> {} -> C", self.1)
-            }
+        match self {
+            Operand::Literal(value) | Operand::Ignored(value) => write!(f, "{value}"),
+            Operand::Register(register) => write!(f, "{register}"),
+            Operand::Unused(value) => write!(f, "unused({value})")
         }
     }
 }
 
-impl std::fmt::Display for Operand {
+impl std::fmt::Display for Register {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Operand::Literal(value) => write!(f, "{value}"),
-            Operand::Register(register) => {
-                let register = match register {
-                    Register::A => "A",
-                    Register::B => "B",
-                    Register::C => "C",
-                };
-                write!(f, "{register}")
-            },
-            Operand::Unused(value) => write!(f, "unused({value})")
+            Register::A => write!(f, "A"),
+            Register::B => write!(f, "B"),
+            Register::C => write!(f, "C"),
         }
     }
 }
@@ -0,0 +1,645 @@
+use std::collections::{BinaryHeap, HashMap};
+
+pub type Lit = i32;
+
+pub struct SatSolver {
+    clauses: Vec<Vec<Lit>>,
+    watches: Vec<Vec<usize>>,
+    values: Vec<i8>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_limits: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    activity_increment: f64,
+    order: BinaryHeap<(u64, usize)>,
+    polarity: Vec<bool>,
+    model: Vec<bool>,
+    unsatisfiable: bool,
+}
+
+pub fn var_of(lit: Lit) -> usize {
+    (lit.unsigned_abs() - 1) as usize
+}
+
+fn watch_index(lit: Lit) -> usize {
+    var_of(lit) * 2 + if lit < 0 { 1 } else { 0 }
+}
+
+impl SatSolver {
+    pub fn new() -> SatSolver {
+        SatSolver {
+            clauses: Vec::new(),
+            watches: Vec::new(),
+            values: Vec::new(),
+            levels: Vec::new(),
+            reasons: Vec::new(),
+            trail: Vec::new(),
+            trail_limits: Vec::new(),
+            propagated: 0,
+            activity: Vec::new(),
+            activity_increment: 1.0,
+            order: BinaryHeap::new(),
+            polarity: Vec::new(),
+            model: Vec::new(),
+            unsatisfiable: false,
+        }
+    }
+
+    pub fn new_var(&mut self) -> Lit {
+        let var = self.values.len();
+        self.values.push(0);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.polarity.push(false);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.order.push((0, var));
+        (var + 1) as Lit
+    }
+
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        if self.unsatisfiable {
+            return;
+        }
+        self.backtrack(0);
+
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for &lit in lits {
+            match self.value(lit) {
+                1 => return,
+                -1 => {}
+                _ => {
+                    if clause.contains(&-lit) {
+                        return;
+                    } else if !clause.contains(&lit) {
+                        clause.push(lit);
+                    }
+                }
+            }
+        }
+
+        match clause.len() {
+            0 => {
+                self.unsatisfiable = true;
+            }
+            1 => {
+                self.assign(clause[0], None);
+                if self.propagate().is_some() {
+                    self.unsatisfiable = true;
+                }
+            }
+            _ => {
+                self.attach(clause);
+            }
+        }
+    }
+
+    pub fn solve(&mut self, assumptions: &[Lit]) -> bool {
+        if self.unsatisfiable {
+            return false;
+        }
+        self.backtrack(0);
+
+        let mut conflicts: u64 = 0;
+        let mut restart_index: u32 = 0;
+        let mut restart_limit = 100 * luby(restart_index);
+        loop {
+            if let Some(conflict) = self.propagate() {
+                conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.unsatisfiable = true;
+                    return false;
+                }
+
+                let (learnt, backtrack_level) = self.analyze(conflict);
+                self.backtrack(backtrack_level);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], None);
+                } else {
+                    let asserting = learnt[0];
+                    let index = self.attach(learnt);
+                    self.assign(asserting, Some(index));
+                }
+                self.activity_increment /= 0.95;
+
+                if conflicts >= restart_limit {
+                    conflicts = 0;
+                    restart_index += 1;
+                    restart_limit = 100 * luby(restart_index);
+                    self.backtrack(0);
+                }
+            } else if self.decision_level() < assumptions.len() {
+                let assumption = assumptions[self.decision_level()];
+                match self.value(assumption) {
+                    1 => {
+                        self.trail_limits.push(self.trail.len());
+                    }
+                    -1 => {
+                        return false;
+                    }
+                    _ => {
+                        self.trail_limits.push(self.trail.len());
+                        self.assign(assumption, None);
+                    }
+                }
+            } else if let Some(var) = self.pick_branch_var() {
+                self.trail_limits.push(self.trail.len());
+                let lit = (var + 1) as Lit;
+                self.assign(if self.polarity[var] { lit } else { -lit }, None);
+            } else {
+                self.model = self.values.iter().map(|&value| value > 0).collect();
+                return true;
+            }
+        }
+    }
+
+    pub fn model(&self) -> &[bool] {
+        &self.model
+    }
+
+    fn value(&self, lit: Lit) -> i8 {
+        let value = self.values[var_of(lit)];
+        if lit > 0 { value } else { -value }
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_limits.len()
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[watch_index(clause[0])].push(index);
+        self.watches[watch_index(clause[1])].push(index);
+        self.clauses.push(clause);
+        index
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = var_of(lit);
+        self.values[var] = if lit > 0 { 1 } else { -1 };
+        self.levels[var] = self.decision_level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = -self.trail[self.propagated];
+            self.propagated += 1;
+
+            let watching = std::mem::take(&mut self.watches[watch_index(false_lit)]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = None;
+            for (i, &index) in watching.iter().enumerate() {
+                if conflict.is_some() {
+                    kept.extend_from_slice(&watching[i..]);
+                    break;
+                }
+
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if self.values[var_of(first)] == if first > 0 { 1 } else { -1 } {
+                    kept.push(index);
+                    continue;
+                }
+
+                let mut moved = false;
+                for k in 2..clause.len() {
+                    let candidate = clause[k];
+                    let value = self.values[var_of(candidate)];
+                    if value == 0 || value == if candidate > 0 { 1 } else { -1 } {
+                        clause.swap(1, k);
+                        self.watches[watch_index(candidate)].push(index);
+                        moved = true;
+                        break;
+                    }
+                }
+                if moved {
+                    continue;
+                }
+
+                kept.push(index);
+                if self.value(first) == -1 {
+                    conflict = Some(index);
+                } else {
+                    self.assign(first, Some(index));
+                }
+            }
+            self.watches[watch_index(false_lit)] = kept;
+
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let current_level = self.decision_level();
+        let mut seen = vec![false; self.values.len()];
+        let mut learnt: Vec<Lit> = vec![0];
+        let mut pending = 0;
+        let mut implied: Option<Lit> = None;
+        let mut clause_index = conflict;
+        let mut trail_index = self.trail.len();
+
+        loop {
+            for k in 0..self.clauses[clause_index].len() {
+                let lit = self.clauses[clause_index][k];
+                if Some(lit) == implied {
+                    continue;
+                }
+                let var = var_of(lit);
+                if !seen[var] && self.levels[var] > 0 {
+                    seen[var] = true;
+                    self.bump(var);
+                    if self.levels[var] == current_level {
+                        pending += 1;
+                    } else {
+                        learnt.push(lit);
+                    }
+                }
+            }
+
+            loop {
+                trail_index -= 1;
+                if seen[var_of(self.trail[trail_index])] {
+                    break;
+                }
+            }
+            let lit = self.trail[trail_index];
+            seen[var_of(lit)] = false;
+            implied = Some(lit);
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = -lit;
+                break;
+            }
+            clause_index = self.reasons[var_of(lit)].unwrap();
+        }
+
+        let mut backtrack_level = 0;
+        if learnt.len() > 1 {
+            let mut max_index = 1;
+            for k in 2..learnt.len() {
+                if self.levels[var_of(learnt[k])] > self.levels[var_of(learnt[max_index])] {
+                    max_index = k;
+                }
+            }
+            learnt.swap(1, max_index);
+            backtrack_level = self.levels[var_of(learnt[1])];
+        }
+        (learnt, backtrack_level)
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.activity_increment;
+        if self.activity[var] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.activity_increment *= 1e-100;
+            self.order = (0..self.values.len())
+                .map(|var| (self.activity[var].to_bits(), var))
+                .collect();
+        } else {
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let limit = self.trail_limits[level];
+        for i in (limit..self.trail.len()).rev() {
+            let lit = self.trail[i];
+            let var = var_of(lit);
+            self.values[var] = 0;
+            self.reasons[var] = None;
+            self.polarity[var] = lit > 0;
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+        self.trail.truncate(limit);
+        self.trail_limits.truncate(level);
+        self.propagated = limit;
+    }
+
+    fn pick_branch_var(&mut self) -> Option<usize> {
+        while let Some((_, var)) = self.order.pop() {
+            if self.values[var] == 0 {
+                return Some(var);
+            }
+        }
+        None
+    }
+}
+
+fn luby(index: u32) -> u64 {
+    let mut size: u64 = 1;
+    let mut sequence: u32 = 0;
+    while size < (index as u64) + 1 {
+        sequence += 1;
+        size = 2 * size + 1;
+    }
+    let mut x = index as u64;
+    while size - 1 != x {
+        size = (size - 1) >> 1;
+        sequence -= 1;
+        x %= size;
+    }
+    1 << sequence
+}
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Bit {
+    Const(bool),
+    Lit(Lit),
+}
+
+impl Bit {
+    pub fn value(self, model: &[bool]) -> bool {
+        match self {
+            Bit::Const(value) => value,
+            Bit::Lit(lit) => model[var_of(lit)] == (lit > 0),
+        }
+    }
+}
+
+#[derive(Eq, Hash, PartialEq)]
+enum Gate {
+    And(Lit, Lit),
+    Xor(Lit, Lit),
+    Mux(Lit, Lit, Lit),
+}
+
+// Collects the clauses of a circuit, so that any backend can solve them
+pub struct BitBlaster {
+    pub clauses: Vec<Vec<Lit>>,
+    vars: usize,
+    gates: HashMap<Gate, Lit>,
+}
+
+impl BitBlaster {
+    pub fn new() -> BitBlaster {
+        BitBlaster {
+            clauses: Vec::new(),
+            vars: 0,
+            gates: HashMap::new(),
+        }
+    }
+
+    pub fn var_count(&self) -> usize {
+        self.vars
+    }
+
+    pub fn fresh(&mut self) -> Bit {
+        Bit::Lit(self.new_var())
+    }
+
+    pub fn solver(&self) -> SatSolver {
+        let mut solver = SatSolver::new();
+        for _ in 0..self.vars {
+            solver.new_var();
+        }
+        for clause in self.clauses.iter() {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+
+    fn new_var(&mut self) -> Lit {
+        self.vars += 1;
+        self.vars as Lit
+    }
+
+    fn add_clause(&mut self, lits: &[Lit]) {
+        self.clauses.push(lits.to_vec());
+    }
+
+    pub fn not(&self, a: Bit) -> Bit {
+        match a {
+            Bit::Const(value) => Bit::Const(!value),
+            Bit::Lit(lit) => Bit::Lit(-lit),
+        }
+    }
+
+    pub fn and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(false), _) | (_, Bit::Const(false)) => Bit::Const(false),
+            (Bit::Const(true), other) | (other, Bit::Const(true)) => other,
+            (Bit::Lit(x), Bit::Lit(y)) => {
+                if x == y {
+                    return a;
+                } else if x == -y {
+                    return Bit::Const(false);
+                }
+                let key = Gate::And(x.min(y), x.max(y));
+                if let Some(&out) = self.gates.get(&key) {
+                    return Bit::Lit(out);
+                }
+                let out = self.new_var();
+                self.add_clause(&[-out, x]);
+                self.add_clause(&[-out, y]);
+                self.add_clause(&[out, -x, -y]);
+                self.gates.insert(key, out);
+                Bit::Lit(out)
+            }
+        }
+    }
+
+    pub fn or(&mut self, a: Bit, b: Bit) -> Bit {
+        let (not_a, not_b) = (self.not(a), self.not(b));
+        let nor = self.and(not_a, not_b);
+        self.not(nor)
+    }
+
+    pub fn or_all(&mut self, bits: &[Bit]) -> Bit {
+        bits.iter().fold(Bit::Const(false), |acc, &bit| self.or(acc, bit))
+    }
+
+    pub fn xor(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(x), Bit::Const(y)) => Bit::Const(x != y),
+            (Bit::Const(false), other) | (other, Bit::Const(false)) => other,
+            (Bit::Const(true), other) | (other, Bit::Const(true)) => self.not(other),
+            (Bit::Lit(x), Bit::Lit(y)) => {
+                if x == y {
+                    return Bit::Const(false);
+                } else if x == -y {
+                    return Bit::Const(true);
+                }
+                let key = Gate::Xor(x.min(y), x.max(y));
+                if let Some(&out) = self.gates.get(&key) {
+                    return Bit::Lit(out);
+                }
+                let out = self.new_var();
+                self.add_clause(&[-out, x, y]);
+                self.add_clause(&[-out, -x, -y]);
+                self.add_clause(&[out, -x, y]);
+                self.add_clause(&[out, x, -y]);
+                self.gates.insert(key, out);
+                Bit::Lit(out)
+            }
+        }
+    }
+
+    pub fn mux(&mut self, select: Bit, then: Bit, otherwise: Bit) -> Bit {
+        if then == otherwise {
+            return then;
+        }
+        match (select, then, otherwise) {
+            (Bit::Const(true), _, _) => then,
+            (Bit::Const(false), _, _) => otherwise,
+            (_, Bit::Const(false), _) => {
+                let not_select = self.not(select);
+                self.and(not_select, otherwise)
+            }
+            (_, _, Bit::Const(false)) => self.and(select, then),
+            (_, Bit::Const(true), _) => self.or(select, otherwise),
+            (_, _, Bit::Const(true)) => {
+                let not_select = self.not(select);
+                self.or(not_select, then)
+            }
+            (Bit::Lit(s), Bit::Lit(t), Bit::Lit(e)) => {
+                let key = Gate::Mux(s, t, e);
+                if let Some(&out) = self.gates.get(&key) {
+                    return Bit::Lit(out);
+                }
+                let out = self.new_var();
+                self.add_clause(&[-s, -t, out]);
+                self.add_clause(&[-s, t, -out]);
+                self.add_clause(&[s, -e, out]);
+                self.add_clause(&[s, e, -out]);
+                self.add_clause(&[-t, -e, out]);
+                self.add_clause(&[t, e, -out]);
+                self.gates.insert(key, out);
+                Bit::Lit(out)
+            }
+        }
+    }
+
+    pub fn assert(&mut self, bit: Bit) {
+        match bit {
+            Bit::Const(true) => {}
+            Bit::Const(false) => self.add_clause(&[]),
+            Bit::Lit(lit) => self.add_clause(&[lit]),
+        }
+    }
+
+    pub fn assert_any(&mut self, bits: &[Bit]) {
+        let mut clause = Vec::new();
+        for &bit in bits {
+            match bit {
+                Bit::Const(true) => return,
+                Bit::Const(false) => {}
+                Bit::Lit(lit) => clause.push(lit),
+            }
+        }
+        self.add_clause(&clause);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solver_with(vars: usize, clauses: &[&[Lit]]) -> SatSolver {
+        let mut solver = SatSolver::new();
+        for _ in 0..vars {
+            solver.new_var();
+        }
+        for clause in clauses {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+
+    fn satisfies(model: &[bool], clauses: &[&[Lit]]) -> bool {
+        clauses.iter().all(|clause| clause.iter().any(|&lit| Bit::Lit(lit).value(model)))
+    }
+
+    #[test]
+    fn solves_satisfiable_cnf() {
+        let clauses: &[&[Lit]] = &[&[1, 2], &[-1, 3], &[-2, -3], &[-3, 4], &[1, -4, 2]];
+        let mut solver = solver_with(4, clauses);
+        assert!(solver.solve(&[]));
+        assert!(satisfies(solver.model(), clauses));
+    }
+
+    #[test]
+    fn detects_unsatisfiable_cnf() {
+        let mut solver = solver_with(2, &[&[1, 2], &[-1, 2], &[1, -2], &[-1, -2]]);
+        assert!(!solver.solve(&[]));
+        let mut solver = solver_with(1, &[&[1], &[-1]]);
+        assert!(!solver.solve(&[]));
+    }
+
+    #[test]
+    fn detects_pigeonhole_conflict() {
+        // three pigeons in two holes: variable 2 * pigeon + hole + 1
+        let pigeon = |p: i32, h: i32| 2 * p + h + 1;
+        let mut clauses: Vec<Vec<Lit>> = (0..3).map(|p| vec![pigeon(p, 0), pigeon(p, 1)]).collect();
+        for h in 0..2 {
+            for p in 0..3 {
+                for q in (p + 1)..3 {
+                    clauses.push(vec![-pigeon(p, h), -pigeon(q, h)]);
+                }
+            }
+        }
+        let clauses: Vec<&[Lit]> = clauses.iter().map(|clause| clause.as_slice()).collect();
+        let mut solver = solver_with(6, &clauses);
+        assert!(!solver.solve(&[]));
+    }
+
+    #[test]
+    fn assumptions_do_not_persist() {
+        let mut solver = solver_with(2, &[&[1, 2]]);
+        assert!(!solver.solve(&[-1, -2]));
+        assert!(solver.solve(&[-1]));
+        assert_eq!(solver.model(), &[false, true]);
+        assert!(solver.solve(&[]));
+    }
+
+    #[test]
+    fn blasted_gates_match_truth_tables() {
+        for (a, b, select) in (0..8).map(|bits| (bits & 1 != 0, bits & 2 != 0, bits & 4 != 0)) {
+            let mut blaster = BitBlaster::new();
+            let (x, y, s) = (blaster.fresh(), blaster.fresh(), blaster.fresh());
+            let and = blaster.and(x, y);
+            let or = blaster.or(x, y);
+            let xor = blaster.xor(x, y);
+            let mux = blaster.mux(s, x, y);
+            for (bit, value) in [(x, a), (y, b), (s, select)] {
+                let bit = if value { bit } else { blaster.not(bit) };
+                blaster.assert(bit);
+            }
+
+            let mut solver = blaster.solver();
+            assert!(solver.solve(&[]));
+            let model = solver.model();
+            assert_eq!(and.value(model), a && b);
+            assert_eq!(or.value(model), a || b);
+            assert_eq!(xor.value(model), a != b);
+            assert_eq!(mux.value(model), if select { a } else { b });
+        }
+    }
+
+    #[test]
+    fn constant_bits_need_no_variables() {
+        let mut blaster = BitBlaster::new();
+        let x = blaster.fresh();
+        assert_eq!(blaster.xor(x, Bit::Const(false)), x);
+        assert_eq!(blaster.xor(x, x), Bit::Const(false));
+        assert_eq!(blaster.and(x, Bit::Const(true)), x);
+        assert_eq!(blaster.mux(Bit::Const(true), x, Bit::Const(false)), x);
+        assert_eq!(blaster.var_count(), 1);
+        assert!(blaster.clauses.is_empty());
+    }
+}
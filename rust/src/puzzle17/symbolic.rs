@@ -0,0 +1,558 @@
+use core::{IntProblem, IntSolver, IntVar, Relation};
+use std::collections::HashMap;
+
+use super::{Instruction, Operand};
+use super::machine::{Action, InstructionSet};
+use super::sat::{var_of, Bit, BitBlaster, Lit};
+
+const WIDTH: usize = 64;
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Node {
+    Input,
+    Const(u64),
+    Xor(usize, usize),
+    Shr(usize, usize),
+    Low3(usize),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Condition {
+    Low3Equals(usize, u64),
+    NonZero(usize),
+    Zero(usize),
+}
+
+pub struct Expressions {
+    nodes: Vec<Node>,
+    lookup: HashMap<Node, usize>,
+}
+
+impl Expressions {
+    pub fn new() -> Expressions {
+        Expressions {
+            nodes: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn input(&mut self) -> usize {
+        self.intern(Node::Input)
+    }
+
+    pub fn constant(&mut self, value: u64) -> usize {
+        self.intern(Node::Const(value))
+    }
+
+    pub fn as_const(&self, id: usize) -> Option<u64> {
+        match self.nodes[id] {
+            Node::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn xor(&mut self, a: usize, b: usize) -> usize {
+        match (self.as_const(a), self.as_const(b)) {
+            (Some(x), Some(y)) => self.constant(x ^ y),
+            (Some(0), _) => b,
+            (_, Some(0)) => a,
+            _ if a == b => self.constant(0),
+            _ => self.intern(Node::Xor(a.min(b), a.max(b))),
+        }
+    }
+
+    pub fn shr(&mut self, value: usize, shift: usize) -> usize {
+        match (self.as_const(value), self.as_const(shift)) {
+            (Some(x), Some(y)) => self.constant(x.checked_shr(y.try_into().unwrap_or(u32::MAX)).unwrap_or(0)),
+            (Some(0), _) => value,
+            (_, Some(0)) => value,
+            (_, Some(y)) if y >= WIDTH as u64 => self.constant(0),
+            _ => self.intern(Node::Shr(value, shift)),
+        }
+    }
+
+    pub fn low3(&mut self, value: usize) -> usize {
+        match self.nodes[value] {
+            Node::Const(x) => self.constant(x % 8),
+            Node::Low3(_) => value,
+            _ => self.intern(Node::Low3(value)),
+        }
+    }
+
+    fn intern(&mut self, node: Node) -> usize {
+        if let Some(&id) = self.lookup.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.lookup.insert(node, id);
+        id
+    }
+}
+
+pub struct SymbolicPath {
+    pub conditions: Vec<Condition>,
+}
+
+struct PathState {
+    registers: [usize; 3],
+    instruction: usize,
+    outputs: usize,
+    conditions: Vec<Condition>,
+    steps: usize,
+}
+
+pub struct SymbolicExecutor<'a> {
+    isa: &'a InstructionSet,
+    program: &'a [u8],
+    pub max_steps: usize,
+    pub max_paths: usize,
+}
+
+impl<'a> SymbolicExecutor<'a> {
+    pub fn new(isa: &'a InstructionSet, program: &'a [u8]) -> SymbolicExecutor<'a> {
+        SymbolicExecutor {
+            isa,
+            program,
+            max_steps: 100_000,
+            max_paths: 10_000,
+        }
+    }
+
+    pub fn explore(
+        &self,
+        expressions: &mut Expressions,
+        register_b: u64,
+        register_c: u64,
+        expected: &[u8],
+    ) -> Result<Vec<SymbolicPath>, String> {
+        let mut stack = vec![PathState {
+            registers: [expressions.input(), expressions.constant(register_b), expressions.constant(register_c)],
+            instruction: 0,
+            outputs: 0,
+            conditions: Vec::new(),
+            steps: 0,
+        }];
+        let mut explored = 0;
+        let mut paths = Vec::new();
+
+        'path: while let Some(mut state) = stack.pop() {
+            explored += 1;
+            if explored > self.max_paths {
+                return Err(format!("Symbolic execution exceeded {} paths", self.max_paths));
+            }
+
+            while state.instruction < self.program.len() {
+                state.steps += 1;
+                if state.steps > self.max_steps {
+                    return Err(format!("Symbolic execution exceeded {} steps on a path", self.max_steps));
+                }
+
+                let index = state.instruction;
+                let (Some(&raw_code), Some(&raw_operand)) = (self.program.get(index), self.program.get(index + 1)) else {
+                    continue 'path;
+                };
+                let Some(Instruction(spec, operand)) = self.isa.decode(raw_code, raw_operand) else {
+                    continue 'path;
+                };
+                let value = match operand {
+                    Operand::Literal(value) => expressions.constant(value.into()),
+                    Operand::Register(register) => state.registers[register as usize],
+                    Operand::Ignored(_) => expressions.constant(0),
+                    Operand::Unused(_) => {
+                        continue 'path;
+                    }
+                };
+
+                let registers = &mut state.registers;
+                match spec.action {
+                    Action::Shr { dest, src } => {
+                        registers[dest as usize] = expressions.shr(registers[src as usize], value);
+                    }
+                    Action::Xor { dest } => {
+                        registers[dest as usize] = expressions.xor(registers[dest as usize], value);
+                    }
+                    Action::XorRegister { dest, src } => {
+                        registers[dest as usize] = expressions.xor(registers[dest as usize], registers[src as usize]);
+                    }
+                    Action::Mod8 { dest } => {
+                        registers[dest as usize] = expressions.low3(value);
+                    }
+                    Action::Set { dest } => {
+                        registers[dest as usize] = value;
+                    }
+                    Action::Jnz { test } => {
+                        let target = expressions.as_const(value)
+                            .and_then(|target| usize::try_from(target).ok())
+                            .ok_or(format!("Symbolic execution does not support the computed jump at #{index}"))?;
+                        let test = registers[test as usize];
+                        match expressions.as_const(test) {
+                            Some(0) => {}
+                            Some(_) => {
+                                state.instruction = target;
+                                continue;
+                            }
+                            None => {
+                                let mut jumped = PathState {
+                                    instruction: target,
+                                    conditions: state.conditions.clone(),
+                                    ..state
+                                };
+                                jumped.conditions.push(Condition::NonZero(test));
+                                state.conditions.push(Condition::Zero(test));
+                                let fallthrough = PathState {
+                                    instruction: index + 2,
+                                    ..state
+                                };
+                                stack.push(fallthrough);
+                                stack.push(jumped);
+                                continue 'path;
+                            }
+                        }
+                    }
+                    Action::Out => {
+                        let Some(&expected_value) = expected.get(state.outputs) else {
+                            continue 'path;
+                        };
+                        let low = expressions.low3(value);
+                        match expressions.as_const(low) {
+                            Some(actual) if actual != expected_value.into() => {
+                                continue 'path;
+                            }
+                            Some(_) => {}
+                            None => {
+                                state.conditions.push(Condition::Low3Equals(low, expected_value.into()));
+                            }
+                        }
+                        state.outputs += 1;
+                    }
+                    Action::In { .. } => {
+                        return Err(format!("Symbolic execution does not support the input instruction at #{index}"));
+                    }
+                    Action::Halt => {
+                        break;
+                    }
+                }
+                state.instruction += 2;
+            }
+
+            if state.outputs == expected.len() {
+                paths.push(SymbolicPath { conditions: state.conditions });
+            }
+        }
+
+        Ok(paths)
+    }
+}
+
+pub trait PathSolver {
+    fn minimize_input(&self, expressions: &Expressions, conditions: &[Condition]) -> Result<Option<u64>, String>;
+}
+
+pub fn default_path_solver() -> Box<dyn PathSolver> {
+    #[cfg(feature = "z3")]
+    return Box::new(Z3PathSolver);
+    #[cfg(not(feature = "z3"))]
+    return Box::new(BitBlastSolver);
+}
+
+pub fn find_min_input(
+    isa: &InstructionSet,
+    program: &[u8],
+    register_b: u64,
+    register_c: u64,
+    expected: &[u8],
+    solver: &dyn PathSolver,
+) -> Result<Option<u64>, String> {
+    let mut expressions = Expressions::new();
+    let executor = SymbolicExecutor::new(isa, program);
+    let paths = executor.explore(&mut expressions, register_b, register_c, expected)?;
+
+    let mut best: Option<u64> = None;
+    for path in paths.iter() {
+        if let Some(value) = solver.minimize_input(&expressions, &path.conditions)? {
+            best = Some(best.map_or(value, |best| best.min(value)));
+        }
+    }
+    Ok(best)
+}
+
+pub struct BitBlastSolver;
+
+impl PathSolver for BitBlastSolver {
+    fn minimize_input(&self, expressions: &Expressions, conditions: &[Condition]) -> Result<Option<u64>, String> {
+        let (blaster, input_bits) = blast_conditions(expressions, conditions);
+        let mut solver = blaster.solver();
+        if !solver.solve(&[]) {
+            return Ok(None);
+        }
+
+        let mut assumptions = Vec::new();
+        for &bit in input_bits.iter().rev() {
+            let Bit::Lit(lit) = bit else {
+                continue;
+            };
+            if !bit.value(solver.model()) {
+                assumptions.push(-lit);
+                continue;
+            }
+            // keep the previous model (with the bit set) if clearing the bit is unsatisfiable
+            assumptions.push(-lit);
+            if !solver.solve(&assumptions) {
+                assumptions.pop();
+                assumptions.push(lit);
+            }
+        }
+        Ok(Some(input_value(&input_bits, solver.model())))
+    }
+}
+
+// Hands the bit-blasted clauses to a core integer solver as 0/1 variables, fixing the
+// input bits from the highest one down to get the minimal input without a 64 bit objective
+pub struct IntPathSolver {
+    pub solver: Box<dyn IntSolver>,
+}
+
+impl IntPathSolver {
+    fn solve_clauses(&self, blaster: &BitBlaster, fixed: &[Lit]) -> Result<Option<Vec<bool>>, String> {
+        let mut problem = IntProblem::new();
+        let vars: Vec<IntVar> = (1..=blaster.var_count())
+            .map(|var| problem.add_var(&format!("x{var}"), 0..=1))
+            .collect();
+        for clause in blaster.clauses.iter().map(|clause| clause.as_slice()).chain(fixed.chunks(1)) {
+            // at least one literal is true: sum(x) + sum(1 - y) >= 1
+            let terms: Vec<(i64, IntVar)> = clause.iter()
+                .map(|&lit| (if lit > 0 { 1 } else { -1 }, vars[var_of(lit)]))
+                .collect();
+            let negative = clause.iter().filter(|&&lit| lit < 0).count() as i64;
+            problem.constrain(&terms, Relation::Ge, 1 - negative);
+        }
+        let solution = self.solver.solve(&problem)?;
+        Ok(solution.map(|solution| vars.iter().map(|&var| solution.get(var) != 0).collect()))
+    }
+}
+
+impl PathSolver for IntPathSolver {
+    fn minimize_input(&self, expressions: &Expressions, conditions: &[Condition]) -> Result<Option<u64>, String> {
+        let (blaster, input_bits) = blast_conditions(expressions, conditions);
+        let mut fixed = Vec::new();
+        let Some(mut model) = self.solve_clauses(&blaster, &fixed)? else {
+            return Ok(None);
+        };
+
+        for &bit in input_bits.iter().rev() {
+            let Bit::Lit(lit) = bit else {
+                continue;
+            };
+            fixed.push(-lit);
+            if bit.value(&model) {
+                match self.solve_clauses(&blaster, &fixed)? {
+                    Some(cleared) => model = cleared,
+                    None => {
+                        fixed.pop();
+                        fixed.push(lit);
+                    }
+                }
+            }
+        }
+        Ok(Some(input_value(&input_bits, &model)))
+    }
+}
+
+// Translates every expression node to bits and asserts the path conditions, returning
+// the bits of the input (none if the path never reads it)
+fn blast_conditions(expressions: &Expressions, conditions: &[Condition]) -> (BitBlaster, Vec<Bit>) {
+    let mut blaster = BitBlaster::new();
+    let mut translated: Vec<Vec<Bit>> = Vec::with_capacity(expressions.nodes().len());
+    let mut input_bits: Vec<Bit> = Vec::new();
+    for node in expressions.nodes() {
+        let bits = match *node {
+            Node::Input => {
+                input_bits = (0..WIDTH).map(|_| blaster.fresh()).collect();
+                input_bits.clone()
+            }
+            Node::Const(value) => (0..WIDTH).map(|i| Bit::Const((value >> i) & 1 != 0)).collect(),
+            Node::Xor(a, b) => (0..WIDTH)
+                .map(|i| blaster.xor(translated[a][i], translated[b][i]))
+                .collect(),
+            Node::Shr(value, shift) => {
+                blast_shift_right(&mut blaster, &translated[value], &translated[shift])
+            }
+            Node::Low3(value) => (0..WIDTH)
+                .map(|i| if i < 3 { translated[value][i] } else { Bit::Const(false) })
+                .collect(),
+        };
+        translated.push(bits);
+    }
+
+    for condition in conditions {
+        match *condition {
+            Condition::Low3Equals(id, expected) => {
+                for (i, &bit) in translated[id].iter().take(3).enumerate() {
+                    let bit = if (expected >> i) & 1 != 0 { bit } else { blaster.not(bit) };
+                    blaster.assert(bit);
+                }
+            }
+            Condition::NonZero(id) => {
+                blaster.assert_any(&translated[id]);
+            }
+            Condition::Zero(id) => {
+                for &bit in translated[id].iter() {
+                    let bit = blaster.not(bit);
+                    blaster.assert(bit);
+                }
+            }
+        }
+    }
+    (blaster, input_bits)
+}
+
+fn input_value(input_bits: &[Bit], model: &[bool]) -> u64 {
+    input_bits.iter().rev().fold(0, |value, bit| (value << 1) | u64::from(bit.value(model)))
+}
+
+fn blast_shift_right(blaster: &mut BitBlaster, value: &[Bit], shift: &[Bit]) -> Vec<Bit> {
+    let mut current = value.to_vec();
+    let stages = WIDTH.trailing_zeros() as usize;
+    for (stage, &select) in shift.iter().take(stages).enumerate() {
+        let amount = 1 << stage;
+        current = (0..WIDTH)
+            .map(|i| {
+                let shifted = current.get(i + amount).copied().unwrap_or(Bit::Const(false));
+                blaster.mux(select, shifted, current[i])
+            })
+            .collect();
+    }
+    let overflow = blaster.or_all(&shift[stages..]);
+    let keep = blaster.not(overflow);
+    current.into_iter().map(|bit| blaster.and(keep, bit)).collect()
+}
+
+#[cfg(feature = "z3")]
+pub struct Z3PathSolver;
+
+#[cfg(feature = "z3")]
+impl PathSolver for Z3PathSolver {
+    fn minimize_input(&self, expressions: &Expressions, conditions: &[Condition]) -> Result<Option<u64>, String> {
+        use z3::ast::{Ast, BV};
+
+        let config = z3::Config::new();
+        let ctx = z3::Context::new(&config);
+        let optimize = z3::Optimize::new(&ctx);
+        let width = WIDTH as u32;
+
+        let input = BV::new_const(&ctx, "a", width);
+        let mut translated: Vec<BV> = Vec::with_capacity(expressions.nodes().len());
+        for node in expressions.nodes() {
+            let value = match *node {
+                Node::Input => input.clone(),
+                Node::Const(value) => BV::from_u64(&ctx, value, width),
+                Node::Xor(a, b) => translated[a].bvxor(&translated[b]),
+                Node::Shr(value, shift) => translated[value].bvlshr(&translated[shift]),
+                Node::Low3(value) => translated[value].bvand(&BV::from_u64(&ctx, 7, width)),
+            };
+            translated.push(value);
+        }
+
+        let zero = BV::from_u64(&ctx, 0, width);
+        for condition in conditions {
+            match *condition {
+                Condition::Low3Equals(id, expected) => {
+                    optimize.assert(&translated[id]._eq(&BV::from_u64(&ctx, expected, width)));
+                }
+                Condition::NonZero(id) => {
+                    optimize.assert(&translated[id]._eq(&zero).not());
+                }
+                Condition::Zero(id) => {
+                    optimize.assert(&translated[id]._eq(&zero));
+                }
+            }
+        }
+        optimize.minimize(&input);
+
+        match optimize.check(&[]) {
+            z3::SatResult::Sat => {
+                let model = optimize.get_model().ok_or("Z3: failed to get model")?;
+                let value = model.eval(&input, true)
+                    .and_then(|value| value.as_u64())
+                    .ok_or("Z3: failed to evaluate register A")?;
+                Ok(Some(value))
+            }
+            z3::SatResult::Unsat => Ok(None),
+            z3::SatResult::Unknown => Err(format!(
+                "Z3: unknown result ({})",
+                optimize.get_reason_unknown().unwrap_or_default()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiled::CompiledProgram;
+    use core::BranchAndBound;
+
+    const SAMPLE: [u8; 6] = [0, 3, 5, 4, 3, 0];
+
+    fn solve(program: &[u8], expected: &[u8], solver: &dyn PathSolver) -> Result<Option<u64>, String> {
+        find_min_input(&InstructionSet::standard(), program, 0, 0, expected, solver)
+    }
+
+    fn int_path_solver() -> IntPathSolver {
+        IntPathSolver { solver: Box::new(BranchAndBound::new()) }
+    }
+
+    #[test]
+    fn bit_blasting_finds_sample_quine() {
+        assert_eq!(solve(&SAMPLE, &SAMPLE, &BitBlastSolver), Ok(Some(117440)));
+    }
+
+    #[test]
+    fn int_solver_finds_sample_quine() {
+        assert_eq!(solve(&SAMPLE, &SAMPLE, &int_path_solver()), Ok(Some(117440)));
+    }
+
+    #[cfg(feature = "z3")]
+    #[test]
+    fn z3_finds_sample_quine() {
+        assert_eq!(solve(&SAMPLE, &SAMPLE, &Z3PathSolver), Ok(Some(117440)));
+    }
+
+    #[test]
+    fn finds_minimal_input_for_shorter_output() {
+        // two iterations output A >> 3 and A >> 6
+        assert_eq!(solve(&SAMPLE, &[5, 0], &BitBlastSolver), Ok(Some(40)));
+        assert_eq!(solve(&SAMPLE, &[5, 0], &int_path_solver()), Ok(Some(40)));
+    }
+
+    #[test]
+    fn reports_unreachable_output() {
+        // out 0 always prints 0
+        assert_eq!(solve(&[5, 0], &[1], &BitBlastSolver), Ok(None));
+        assert_eq!(solve(&[5, 0], &[1], &int_path_solver()), Ok(None));
+    }
+
+    #[test]
+    fn solves_variable_shifts() {
+        // bst A, bxl 3, cdv B, bxc, out B, adv 3, jnz 0
+        let program = [2, 4, 1, 3, 7, 5, 4, 0, 5, 5, 0, 3, 3, 0];
+        let expected = run(&program, 0o3517);
+        let found = solve(&program, &expected, &BitBlastSolver).unwrap().unwrap();
+        let brute_force = (0..1 << 12).find(|&a| run(&program, a) == expected);
+        assert_eq!(Some(found), brute_force);
+        assert_eq!(solve(&program, &expected, &int_path_solver()), Ok(Some(found)));
+    }
+
+    fn run(program: &[u8], register_a: u64) -> Vec<u8> {
+        let mut output = Vec::new();
+        CompiledProgram::compile(&InstructionSet::standard(), program)
+            .run([register_a, 0, 0], &mut output)
+            .unwrap();
+        output
+    }
+}
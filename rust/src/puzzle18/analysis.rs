@@ -0,0 +1,213 @@
+use core::Grid;
+use std::collections::VecDeque;
+
+const UNLIMITED: i32 = i32::MAX;
+const NEIGHBORS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+const SURROUNDING: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)];
+
+pub fn reachable(ram: &Grid<char>, from: (i32, i32)) -> Grid<bool> {
+    let mut seen = Grid::new(ram.width(), ram.height(), false);
+    if ram.get(from) != Some('.') {
+        return seen;
+    }
+    seen.set(from, true);
+    let mut queue = VecDeque::from([from]);
+    while let Some((x, y)) = queue.pop_front() {
+        for (dx, dy) in NEIGHBORS {
+            let next = (x + dx, y + dy);
+            if ram.get(next) == Some('.') && seen.get(next) == Some(false) {
+                seen.set(next, true);
+                queue.push_back(next);
+            }
+        }
+    }
+    seen
+}
+
+struct FlowEdge {
+    to: usize,
+    capacity: i32,
+    reverse: usize,
+}
+
+struct FlowNetwork {
+    edges: Vec<Vec<FlowEdge>>,
+}
+
+impl FlowNetwork {
+    fn add_edge(&mut self, from: usize, to: usize, capacity: i32) {
+        let reverse = self.edges[to].len();
+        self.edges[from].push(FlowEdge { to, capacity, reverse });
+        let reverse = self.edges[from].len() - 1;
+        self.edges[to].push(FlowEdge { to: from, capacity: 0, reverse });
+    }
+
+    // Returns the predecessor edges of every node reached through the residual network
+    fn residual_search(&self, source: usize) -> Vec<Option<(usize, usize)>> {
+        let mut from: Vec<Option<(usize, usize)>> = vec![None; self.edges.len()];
+        let mut seen = vec![false; self.edges.len()];
+        seen[source] = true;
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            for (index, edge) in self.edges[node].iter().enumerate() {
+                if edge.capacity > 0 && !seen[edge.to] {
+                    seen[edge.to] = true;
+                    from[edge.to] = Some((node, index));
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+        from[source] = Some((source, usize::MAX));
+        from
+    }
+
+    fn max_flow(&mut self, source: usize, sink: usize, limit: i32) -> i32 {
+        let mut flow = 0;
+        while flow < limit {
+            let from = self.residual_search(source);
+            if from[sink].is_none() {
+                break;
+            }
+            let mut path = Vec::new();
+            let mut node = sink;
+            while node != source {
+                let (previous, index) = from[node].unwrap();
+                path.push((previous, index));
+                node = previous;
+            }
+            let amount = path.iter().map(|&(node, index)| self.edges[node][index].capacity).min().unwrap();
+            for (node, index) in path {
+                self.edges[node][index].capacity -= amount;
+                let FlowEdge { to, reverse, .. } = self.edges[node][index];
+                self.edges[to][reverse].capacity += amount;
+            }
+            flow = flow.saturating_add(amount);
+        }
+        flow
+    }
+}
+
+// Every free cell is split into an "in" and an "out" node joined by a unit edge, so that
+// the minimum cut of the network counts the cells that have to be blocked
+pub fn min_vertex_cut(ram: &Grid<char>, start: (i32, i32), end: (i32, i32)) -> Result<Vec<(i32, i32)>, String> {
+    for (name, (x, y)) in [("Start", start), ("End", end)] {
+        if ram.get((x, y)) != Some('.') {
+            return Err(format!("{name} {x},{y} is blocked or outside the memory space"));
+        }
+    }
+    if start == end {
+        return Err("Start and end are the same cell, no set of cells separates them".to_string());
+    }
+    let cell_index = |(x, y): (i32, i32)| (y * ram.width() + x) as usize;
+    let cell_count = (ram.width() * ram.height()) as usize;
+    let mut network = FlowNetwork { edges: (0..cell_count * 2).map(|_| Vec::new()).collect() };
+    for y in 0..ram.height() {
+        for x in 0..ram.width() {
+            if ram.get((x, y)) != Some('.') {
+                continue;
+            }
+            let index = cell_index((x, y));
+            let capacity = if (x, y) == start || (x, y) == end { UNLIMITED } else { 1 };
+            network.add_edge(index * 2, index * 2 + 1, capacity);
+            for (dx, dy) in NEIGHBORS {
+                let next = (x + dx, y + dy);
+                if ram.get(next) == Some('.') {
+                    network.add_edge(index * 2 + 1, cell_index(next) * 2, UNLIMITED);
+                }
+            }
+        }
+    }
+
+    let source = cell_index(start) * 2;
+    let sink = cell_index(end) * 2 + 1;
+    let flow = network.max_flow(source, sink, cell_count as i32 + 1);
+    if flow > cell_count as i32 {
+        return Err("Start and end are adjacent, no set of cells separates them".to_string());
+    }
+
+    let from_source = network.residual_search(source);
+    let cut = (0..ram.height())
+        .flat_map(|y| (0..ram.width()).map(move |x| (x, y)))
+        .filter(|&cell| {
+            let index = cell_index(cell);
+            from_source[index * 2].is_some() && from_source[index * 2 + 1].is_none() && ram.get(cell) == Some('.')
+        })
+        .collect();
+    Ok(cut)
+}
+
+pub fn reopening_bytes(blocked: &Grid<char>, start: (i32, i32), end: (i32, i32)) -> Vec<(i32, i32)> {
+    let from_start = reachable(blocked, start);
+    let from_end = reachable(blocked, end);
+    let touches = |region: &Grid<bool>, cell: (i32, i32), target: (i32, i32)| {
+        cell == target || NEIGHBORS.iter().any(|&(dx, dy)| region.get((cell.0 + dx, cell.1 + dy)) == Some(true))
+    };
+    (0..blocked.height())
+        .flat_map(|y| (0..blocked.width()).map(move |x| (x, y)))
+        .filter(|&cell| blocked.get(cell) == Some('#'))
+        .filter(|&cell| touches(&from_start, cell, start) && touches(&from_end, cell, end))
+        .collect()
+}
+
+// Bytes along the edge of the region still reachable from the start, chained to the last fallen
+// one diagonally too, as movement is only orthogonal
+pub fn blockade_wall(blocked: &Grid<char>, start: (i32, i32), last_byte: (i32, i32)) -> Vec<(i32, i32)> {
+    let from_start = reachable(blocked, start);
+    let borders_start = |(x, y): (i32, i32)| {
+        (x, y) == start || SURROUNDING.iter().any(|&(dx, dy)| from_start.get((x + dx, y + dy)) == Some(true))
+    };
+
+    let mut seen = Grid::new(blocked.width(), blocked.height(), false);
+    seen.set(last_byte, true);
+    let mut queue = VecDeque::from([last_byte]);
+    let mut wall = Vec::new();
+    while let Some((x, y)) = queue.pop_front() {
+        wall.push((x, y));
+        for (dx, dy) in SURROUNDING {
+            let next = (x + dx, y + dy);
+            if blocked.get(next) == Some('#') && seen.get(next) == Some(false) && borders_start(next) {
+                seen.set(next, true);
+                queue.push_back(next);
+            }
+        }
+    }
+    wall.sort_by_key(|&(x, y)| (y, x));
+    wall
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_BYTES: &str = "5,4 4,2 4,5 3,0 2,1 6,3 2,4 1,5 0,6 3,3 2,6 5,1 1,2 5,5 2,5 6,5 1,4 0,4 6,4 1,1 6,1";
+
+    fn sample_ram(byte_count: usize) -> Grid<char> {
+        let mut ram = Grid::new(7, 7, '.');
+        for byte in SAMPLE_BYTES.split(' ').take(byte_count) {
+            let (x, y) = byte.split_once(',').unwrap();
+            ram.set((x.parse().unwrap(), y.parse().unwrap()), '#');
+        }
+        ram
+    }
+
+    #[test]
+    fn finds_min_vertex_cut_before_blocking_byte() {
+        let ram = sample_ram(20);
+        assert_eq!(min_vertex_cut(&ram, (0, 0), (6, 6)), Ok(vec![(0, 1)]));
+    }
+
+    #[test]
+    fn rejects_degenerate_cut_requests() {
+        let ram = sample_ram(20);
+        assert!(min_vertex_cut(&ram, (0, 0), (0, 0)).is_err());
+        assert!(min_vertex_cut(&ram, (0, 0), (1, 0)).is_err());
+        assert!(min_vertex_cut(&ram, (5, 4), (6, 6)).is_err());
+        assert!(min_vertex_cut(&ram, (0, 0), (7, 7)).is_err());
+    }
+
+    #[test]
+    fn finds_bytes_reopening_the_path() {
+        let blocked = sample_ram(21);
+        assert_eq!(reopening_bytes(&blocked, (0, 0), (6, 6)), vec![(5, 1), (6, 1), (4, 2), (3, 3), (2, 4)]);
+    }
+}
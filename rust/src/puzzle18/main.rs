@@ -1,28 +1,48 @@
-use core::{get_data_path, AStar, AStarGraph, AStarNode, Grid};
-use std::{fmt::Debug, fs::{read_to_string, File}, io::{LineWriter, Write}};
+use core::{get_data_path, AStar, AStarGraph, AStarNode, Grid, split_options};
+use setup::RamSetup;
+use std::{env, fmt::Debug, fs::{read_to_string, File}, io::{LineWriter, Write}};
+use std::path::PathBuf;
+use std::process::exit;
+use timed::{TimedRamGraph, TimedStep};
+
+mod analysis;
+mod setup;
+mod timed;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (positional, options) = split_options(&args[1..], &["--size", "--take", "--start", "--end"]);
+    let input_path = positional.first()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_data_path("input/puzzle18.txt"));
+    let setup = read_to_string(&input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))
+        .and_then(|input| RamSetup::parse(&input, &options))
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {err}");
+            exit(1);
+        });
+
     use std::time::Instant;
     let before = Instant::now();
-    basic();
-    advanced();
+    if options.contains_key("--timed") {
+        timed(&setup);
+    } else if options.contains_key("--analyze") {
+        analyze(&setup);
+    } else {
+        basic(&setup);
+        advanced(&setup);
+    }
     println!("Elapsed time: {:.2?}", before.elapsed());
 }
 
-fn basic() {
-    let input = read_to_string(get_data_path("input/puzzle18.txt")).unwrap();
-    // let grid_size = 7;
-    // let take_bytes = 12;
-    let grid_size = 71;
-    let take_bytes = 1024;
-    let mut ram = Grid::new(grid_size, grid_size, '.');
-    let falling_bytes = parse_falling_bytes(&input);
-
-    for byte in falling_bytes.iter().take(take_bytes) {
+fn basic(setup: &RamSetup) {
+    let mut ram = Grid::new(setup.width, setup.height, '.');
+    for byte in setup.falling_bytes.iter().take(setup.take) {
         ram.set(*byte, '#');
     }
 
-    let graph = RamGraph::new(&ram);
+    let graph = RamGraph::new(&ram, setup.start, setup.end());
     let mut astar = AStar::new(graph);
     loop {
         if astar.next() {
@@ -53,15 +73,25 @@ fn basic() {
     }
 }
 
-fn advanced() {
-    let input = read_to_string(get_data_path("input/puzzle18.txt")).unwrap();
-    // let grid_size = 7;
-    let grid_size = 71;
-    let mut ram = Grid::new(grid_size, grid_size, '.');
-    let falling_bytes = parse_falling_bytes(&input);
+fn advanced(setup: &RamSetup) {
+    let (min_ns, iterations) = find_blocking_byte(setup);
+    match setup.falling_bytes.get(min_ns) {
+        Some((block_x, block_y)) => {
+            println!("First byte which will block the escape is ({block_x},{block_y}) at #{min_ns}");
+        }
+        None => {
+            println!("None of the falling bytes blocks the escape");
+        }
+    }
+    println!("(found in {iterations} iterations)");
+}
+
+fn find_blocking_byte(setup: &RamSetup) -> (usize, usize) {
+    let mut ram = Grid::new(setup.width, setup.height, '.');
+    let falling_bytes = &setup.falling_bytes;
 
     let mut min_ns = 0;
-    let mut max_ns = falling_bytes.len();
+    let mut max_ns = falling_bytes.len() + 1;
     let mut iterations = 0;
     while (max_ns - min_ns) > 1 {
         let take_count = (min_ns + max_ns) / 2;
@@ -71,42 +101,118 @@ fn advanced() {
             ram.set(*byte, '#');
         }
 
-        let graph = RamGraph::new(&ram);
-        let mut astar = AStar::new(graph);
-        loop {
-            if astar.next() {
-                break;
+        // A byte falling on the start or the end blocks the escape, even though the graph never checks them
+        let escapes = [setup.start, setup.end()].iter().all(|&cell| ram.get(cell) != Some('#')) && {
+            let graph = RamGraph::new(&ram, setup.start, setup.end());
+            let mut astar = AStar::new(graph);
+            loop {
+                if astar.next() {
+                    break;
+                }
             }
-        }
+            astar.found_goal().is_some()
+        };
 
-        match astar.found_goal() {
-            Some(_) => {
-                min_ns = take_count;
-            }
-            None => {
-                max_ns = take_count;
-            }
+        if escapes {
+            min_ns = take_count;
+        } else {
+            max_ns = take_count;
         }
         iterations += 1;
     }
+    (min_ns, iterations)
+}
 
-    let (block_x, block_y) = falling_bytes[min_ns];
-    println!("First byte which will block the escape is ({block_x},{block_y}) at #{min_ns}");
-    println!("(found in {iterations} iterations)");
+fn analyze(setup: &RamSetup) {
+    let (min_ns, _) = find_blocking_byte(setup);
+    let Some(&last_byte) = setup.falling_bytes.get(min_ns) else {
+        println!("None of the falling bytes blocks the escape");
+        return;
+    };
+    let (start, end) = (setup.start, setup.end());
+
+    let mut ram = Grid::new(setup.width, setup.height, '.');
+    for byte in setup.falling_bytes.iter().take(min_ns) {
+        ram.set(*byte, '#');
+    }
+    match analysis::min_vertex_cut(&ram, start, end) {
+        Ok(cut) => {
+            let cells: Vec<String> = cut.iter().map(|(x, y)| format!("{x},{y}")).collect();
+            println!("Minimal cut before byte #{min_ns}: {} cells ({})", cut.len(), cells.join(" "));
+        }
+        Err(err) => println!("No minimal cut before byte #{min_ns}: {err}"),
+    }
+
+    let mut blocked = ram.clone();
+    blocked.set(last_byte, '#');
+    let reopening = analysis::reopening_bytes(&blocked, start, end);
+    let cells: Vec<String> = reopening.iter().map(|(x, y)| format!("{x},{y}")).collect();
+    println!("Bytes whose removal reopens the path: {} ({})", reopening.len(), cells.join(" "));
+
+    let wall = analysis::blockade_wall(&blocked, start, last_byte);
+    let mut rendered = blocked.clone();
+    for &cell in wall.iter() {
+        rendered.set(cell, '=');
+    }
+    for &cell in reopening.iter() {
+        rendered.set(cell, '+');
+    }
+    rendered.set(last_byte, '*');
+    rendered.set(start, 'S');
+    rendered.set(end, 'E');
+
+    let mut writer = LineWriter::new(
+        File::create(get_data_path("output/puzzle18_blockade.txt")).unwrap()
+    );
+    for line in rendered.lines() {
+        writer.write_all(line.as_bytes()).unwrap();
+    }
+    println!("Blockade wall of {} bytes ('*' last byte, '+' reopening bytes, '=' rest of the wall)", wall.len());
 }
 
-fn parse_falling_bytes(input: &str) -> Vec<(i32, i32)> {
-    input.lines()
-        .filter(|line| !line.is_empty())
-        .map(|line| {
-            let coords: Vec<&str> = line.split(',').collect();
-            return (coords[0].parse().unwrap(), coords[1].parse().unwrap());
-        })
-        .collect()
+fn timed(setup: &RamSetup) {
+    let falling_bytes = &setup.falling_bytes;
+    let graph = TimedRamGraph::new(setup.width, setup.height, falling_bytes, setup.start, setup.end());
+    let horizon = graph.horizon();
+    let mut astar = AStar::new(graph);
+    loop {
+        if astar.next() {
+            break;
+        }
+    }
+
+    let Some((final_node, final_cost)) = astar.found_goal() else {
+        println!("No escape is possible while the bytes are falling (timed).");
+        return;
+    };
+
+    let mut path: Vec<_> = astar.iter_back_path(final_node.key()).collect();
+    path.reverse();
+    let mut path_writer = LineWriter::new(
+        File::create(get_data_path("output/puzzle18_timed.txt")).unwrap()
+    );
+    for (tick, (node, step)) in path.iter().enumerate() {
+        let action = match step {
+            Some(step) => step.to_char(),
+            None => '@',
+        };
+        let fallen = match tick {
+            0 => String::new(),
+            tick if tick <= horizon as usize => {
+                let (x, y) = falling_bytes[tick - 1];
+                format!(" (byte #{} falls at {x},{y})", tick - 1)
+            }
+            _ => String::new(),
+        };
+        writeln!(path_writer, "{tick:>5} {action} {},{}{fallen}", node.x, node.y).unwrap();
+    }
+
+    let waits = path.iter().filter(|(_, step)| matches!(step, Some(TimedStep::Wait))).count();
+    println!("Fastest escape racing the falling bytes (timed): {final_cost} ticks, {waits} waits");
 }
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
-enum Direction { North, East, South, West }
+pub enum Direction { North, East, South, West }
 
 impl Direction {
     fn offset(&self) -> (i32, i32) {
@@ -147,9 +253,7 @@ impl AStarNode for RamNode {
 }
 
 impl<'a> RamGraph<'a> {
-    fn new(ram: &Grid<char>) -> RamGraph {
-        let start = (0, 0);
-        let end = (ram.width() - 1, ram.height() - 1);
+    fn new(ram: &'a Grid<char>, start: (i32, i32), end: (i32, i32)) -> RamGraph<'a> {
         RamGraph { ram, start, end, debug: false }
     }
 }
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+pub struct RamSetup {
+    pub width: i32,
+    pub height: i32,
+    pub take: usize,
+    pub start: (i32, i32),
+    pub end: Option<(i32, i32)>,
+    pub falling_bytes: Vec<(i32, i32)>,
+}
+
+impl RamSetup {
+    // The input may start with optional "key: value" header lines (size, take, start, end),
+    // command-line options take precedence over the header
+    pub fn parse(input: &str, options: &HashMap<&str, &str>) -> Result<RamSetup, String> {
+        let mut setup = RamSetup {
+            width: 71,
+            height: 71,
+            take: 1024,
+            start: (0, 0),
+            end: None,
+            falling_bytes: Vec::new(),
+        };
+
+        let mut lines = input.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+        while let Some((i, line)) = lines.next_if(|(_, line)| line.contains(':')) {
+            let (key, value) = line.split_once(':').unwrap();
+            setup.apply(key.trim(), value.trim()).map_err(|err| format!("Line {}: {err}", i + 1))?;
+        }
+        for (key, value) in options.iter() {
+            if let Some(key) = key.strip_prefix("--") {
+                if ["size", "take", "start", "end"].contains(&key) {
+                    setup.apply(key, value).map_err(|err| format!("Option --{key}: {err}"))?;
+                }
+            }
+        }
+
+        let in_grid = |(x, y): (i32, i32)| x >= 0 && x < setup.width && y >= 0 && y < setup.height;
+        for (i, line) in lines {
+            let byte = parse_position(line).map_err(|err| format!("Line {}: {err}", i + 1))?;
+            if !in_grid(byte) {
+                return Err(format!(
+                    "Line {}: byte {},{} lies outside the {}x{} grid",
+                    i + 1, byte.0, byte.1, setup.width, setup.height
+                ));
+            }
+            setup.falling_bytes.push(byte);
+        }
+        for (name, (x, y)) in [("Start", setup.start), ("End", setup.end())] {
+            if !in_grid((x, y)) {
+                return Err(format!("{name} {x},{y} lies outside the {}x{} grid", setup.width, setup.height));
+            }
+            if let Some(i) = setup.falling_bytes.iter().take(setup.take).position(|&byte| byte == (x, y)) {
+                return Err(format!("{name} {x},{y} is covered by byte #{i}"));
+            }
+        }
+        Ok(setup)
+    }
+
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "size" => {
+                let (width, height) = value.split_once('x').unwrap_or((value, value));
+                let parse = |value: &str| value.trim().parse::<i32>().ok().filter(|&n| n > 0);
+                let (Some(width), Some(height)) = (parse(width), parse(height)) else {
+                    return Err(format!("invalid size '{value}', expected N or WxH"));
+                };
+                self.width = width;
+                self.height = height;
+            }
+            "take" => {
+                self.take = value.parse().map_err(|_| format!("invalid byte count '{value}'"))?;
+            }
+            "start" => self.start = parse_position(value)?,
+            "end" => self.end = Some(parse_position(value)?),
+            other => {
+                return Err(format!("unknown header key '{other}'"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn end(&self) -> (i32, i32) {
+        self.end.unwrap_or((self.width - 1, self.height - 1))
+    }
+}
+
+fn parse_position(value: &str) -> Result<(i32, i32), String> {
+    let invalid = || format!("invalid position '{value}', expected X,Y");
+    let (x, y) = value.split_once(',').ok_or_else(invalid)?;
+    let x = x.trim().parse::<i32>().map_err(|_| invalid())?;
+    let y = y.trim().parse::<i32>().map_err(|_| invalid())?;
+    Ok((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "size: 7\ntake: 3\n5,4\n4,2\n4,5\n3,0\n";
+
+    #[test]
+    fn rejects_start_and_end_under_taken_bytes() {
+        let parse = |options: &[(&str, &str)]| RamSetup::parse(SAMPLE, &options.iter().copied().collect());
+        assert_eq!(parse(&[("--start", "5,4")]).err(), Some("Start 5,4 is covered by byte #0".to_string()));
+        assert_eq!(parse(&[("--end", "4,5")]).err(), Some("End 4,5 is covered by byte #2".to_string()));
+        assert_eq!(parse(&[("--start", "7,0")]).err(), Some("Start 7,0 lies outside the 7x7 grid".to_string()));
+
+        // Bytes beyond the taken ones may still fall on the start later
+        let setup = parse(&[("--start", "3,0")]).unwrap();
+        assert_eq!((setup.start, setup.end(), setup.falling_bytes.len()), ((3, 0), (6, 6), 4));
+    }
+}
@@ -0,0 +1,100 @@
+use core::{AStarGraph, AStarNode, Grid};
+
+use super::Direction;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TimedStep {
+    Move(Direction),
+    Wait,
+}
+
+impl TimedStep {
+    pub fn to_char(self) -> char {
+        match self {
+            TimedStep::Move(Direction::North) => '^',
+            TimedStep::Move(Direction::East) => '>',
+            TimedStep::Move(Direction::South) => 'v',
+            TimedStep::Move(Direction::West) => '<',
+            TimedStep::Wait => '.',
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TimedNode {
+    pub x: i32,
+    pub y: i32,
+    pub time: u32,
+}
+
+pub struct TimedRamGraph {
+    fall_ticks: Grid<Option<u32>>,
+    horizon: u32,
+    start: (i32, i32),
+    end: (i32, i32),
+}
+
+impl AStarNode for TimedNode {
+    type Key = (i32, i32, u32);
+
+    fn key(&self) -> Self::Key {
+        (self.x, self.y, self.time)
+    }
+}
+
+impl TimedRamGraph {
+    // Byte #i falls at tick i + 1, and its cell stays blocked from that tick on
+    pub fn new(width: i32, height: i32, falling_bytes: &[(i32, i32)], start: (i32, i32), end: (i32, i32)) -> TimedRamGraph {
+        let mut fall_ticks: Grid<Option<u32>> = Grid::new(width, height, None);
+        for (i, &byte) in falling_bytes.iter().enumerate().rev() {
+            fall_ticks.set(byte, Some(i as u32 + 1));
+        }
+        TimedRamGraph { fall_ticks, horizon: falling_bytes.len() as u32, start, end }
+    }
+
+    pub fn horizon(&self) -> u32 {
+        self.horizon
+    }
+
+    fn is_free(&self, at: (i32, i32), time: u32) -> bool {
+        match self.fall_ticks.get(at) {
+            Some(Some(fall_tick)) => time < fall_tick,
+            Some(None) => true,
+            None => false,
+        }
+    }
+}
+
+impl AStarGraph<TimedNode> for TimedRamGraph {
+    type Edge = TimedStep;
+    type Cost = u32;
+
+    fn start(&self) -> TimedNode {
+        let (x, y) = self.start;
+        TimedNode { x, y, time: 0 }
+    }
+
+    fn neighbors(&self, node: &TimedNode) -> impl Iterator<Item = (TimedNode, TimedStep, Self::Cost)> + '_ {
+        // Once every byte has fallen the grid is static, so later ticks collapse into one state
+        let time = (node.time + 1).min(self.horizon + 1);
+        let (x, y) = (node.x, node.y);
+        [Direction::North, Direction::East, Direction::South, Direction::West]
+            .into_iter()
+            .map(move |direction| {
+                let offset = direction.offset();
+                ((x + offset.0, y + offset.1), TimedStep::Move(direction))
+            })
+            .chain([((x, y), TimedStep::Wait)])
+            .filter(move |&(next, step)| self.is_free(next, time) && !(step == TimedStep::Wait && time > self.horizon))
+            .map(move |((x, y), step)| (TimedNode { x, y, time }, step, 1))
+    }
+
+    fn estimate(&self, node: &TimedNode) -> Self::Cost {
+        let (end_x, end_y) = self.end;
+        end_x.abs_diff(node.x) + end_y.abs_diff(node.y)
+    }
+
+    fn is_goal(&self, node: &TimedNode) -> bool {
+        (node.x, node.y) == self.end
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+
+pub trait ArrangementCount: Clone + fmt::Display {
+    fn zero_like(&self) -> Self;
+    fn is_zero(&self) -> bool;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+}
+
+impl ArrangementCount for u64 {
+    fn zero_like(&self) -> Self {
+        0
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u64::checked_add(*self, *other)
+    }
+}
+
+impl ArrangementCount for u128 {
+    fn zero_like(&self) -> Self {
+        0
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u128::checked_add(*self, *other)
+    }
+}
+
+#[derive(Clone)]
+pub struct BigCount {
+    limbs: Vec<u32>,
+}
+
+impl BigCount {
+    pub fn one() -> BigCount {
+        BigCount { limbs: vec![1] }
+    }
+}
+
+impl ArrangementCount for BigCount {
+    fn zero_like(&self) -> Self {
+        BigCount { limbs: Vec::new() }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = u64::from(*self.limbs.get(i).unwrap_or(&0)) + u64::from(*other.limbs.get(i).unwrap_or(&0)) + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        Some(BigCount { limbs })
+    }
+}
+
+impl fmt::Display for BigCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Peel off base 10^9 digits by repeated long division
+        let mut limbs = self.limbs.clone();
+        let mut chunks = Vec::new();
+        while !limbs.is_empty() {
+            let mut remainder = 0u64;
+            for limb in limbs.iter_mut().rev() {
+                let value = (remainder << 32) | u64::from(*limb);
+                *limb = (value / 1_000_000_000) as u32;
+                remainder = value % 1_000_000_000;
+            }
+            chunks.push(remainder);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{first}")?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{chunk:09}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl Modular {
+    pub fn one(modulus: u64) -> Modular {
+        Modular { value: 1 % modulus, modulus }
+    }
+}
+
+impl ArrangementCount for Modular {
+    fn zero_like(&self) -> Self {
+        Modular { value: 0, modulus: self.modulus }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let value = (u128::from(self.value) + u128::from(other.value)) % u128::from(self.modulus);
+        Some(Modular { value: value as u64, modulus: self.modulus })
+    }
+}
+
+impl fmt::Display for Modular {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (mod {})", self.value, self.modulus)
+    }
+}
@@ -1,16 +1,43 @@
-use core::get_data_path;
-use std::{collections::{HashMap, HashSet}, fs::read_to_string, str::FromStr};
+use core::{get_data_path, split_options};
+use count::{ArrangementCount, BigCount, Modular};
+use matcher::TowelMatcher;
+use std::{env, fs::read_to_string, str::FromStr};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::exit;
+
+mod count;
+mod matcher;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (positional, options) = split_options(&args[1..], &["--arrangements", "--count"]);
+    let input_path = positional.first()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_data_path("input/puzzle19.txt"));
+    let input = read_to_string(&input_path).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to read {}: {err}", input_path.display());
+        exit(1);
+    });
+
     use std::time::Instant;
     let before = Instant::now();
-    basic();
-    advanced();
-    println!("Elapsed time: {:.2?}", before.elapsed());
+    let result = if options.contains_key("--report") {
+        report(&input, &options)
+    } else {
+        basic(&input);
+        advanced(&input, &options)
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {err}");
+        exit(1);
+    }
+    if !options.contains_key("--report") {
+        println!("Elapsed time: {:.2?}", before.elapsed());
+    }
 }
 
-fn basic() {
-    let input = read_to_string(get_data_path("input/puzzle19.txt")).unwrap();
+fn basic(input: &str) {
     let puzzle = input.parse::<TowelPuzzle>().unwrap();
 
     let mut matcher = TowelMatcher::new();
@@ -20,7 +47,7 @@ fn basic() {
 
     let mut total_matches = 0;
     for design in puzzle.designs.iter() {
-        if matcher.match_design(design) > 0 {
+        if matcher.is_possible(design) {
             total_matches += 1;
         }
     }
@@ -28,24 +55,82 @@ fn basic() {
     println!("Total valid towel designs (basic): {total_matches}");
 }
 
-fn advanced() {
-    let input = read_to_string(get_data_path("input/puzzle19.txt")).unwrap();
-    let puzzle = input.parse::<TowelPuzzle>().unwrap();
+fn advanced(input: &str, options: &HashMap<&str, &str>) -> Result<(), String> {
+    let puzzle = input.parse::<TowelPuzzle>()?;
 
-    let mut matcher: TowelMatcher<'_> = TowelMatcher::new();
-    let mut ordered_towels = puzzle.towels.clone();
-    ordered_towels.sort_by_key(|towel| towel.len());
-    for towel in ordered_towels.iter() {
-        matcher.add_match(&towel);
+    let mut matcher = TowelMatcher::new();
+    for towel in puzzle.towels.iter() {
+        matcher.add_match(towel);
     }
 
-    let mut total_variants = 0;
-    for design in puzzle.designs.iter() {
-        let count = matcher.match_design(design);
-        total_variants += count;
-    }
+    let count_type = options.get("--count").copied().unwrap_or("u64");
+    let total_variants = match count_type {
+        "u64" => count_variants(&matcher, &puzzle.designs, &1u64, count_type)?,
+        "u128" => count_variants(&matcher, &puzzle.designs, &1u128, count_type)?,
+        "big" => count_variants(&matcher, &puzzle.designs, &BigCount::one(), count_type)?,
+        other => match other.strip_prefix("mod:").and_then(|modulus| modulus.parse::<u64>().ok()) {
+            Some(modulus) if modulus > 0 => {
+                count_variants(&matcher, &puzzle.designs, &Modular::one(modulus), count_type)?
+            }
+            _ => {
+                return Err(format!("Invalid --count value: {other}, expected u64, u128, big or mod:<modulus>"));
+            }
+        },
+    };
 
     println!("Total towel designs variants (advanced): {total_variants}");
+    Ok(())
+}
+
+fn count_variants<C: ArrangementCount>(matcher: &TowelMatcher, designs: &[String], one: &C, count_type: &str) -> Result<String, String> {
+    let mut total_variants = one.zero_like();
+    for design in designs.iter() {
+        let count = matcher.match_design(design, one)
+            .ok_or(format!("Arrangement count of design '{design}' overflows {count_type}"))?;
+        total_variants = total_variants.checked_add(&count)
+            .ok_or(format!("Total arrangement count overflows {count_type} when adding design '{design}'"))?;
+    }
+    Ok(total_variants.to_string())
+}
+
+fn report(input: &str, options: &HashMap<&str, &str>) -> Result<(), String> {
+    let puzzle = input.parse::<TowelPuzzle>()?;
+    let limit = match options.get("--arrangements") {
+        Some(value) => value.parse::<usize>().map_err(|_| format!("Invalid --arrangements value: {value}"))?,
+        None => 3,
+    };
+
+    let mut matcher = TowelMatcher::new();
+    for towel in puzzle.towels.iter() {
+        matcher.add_match(towel);
+    }
+
+    for design in puzzle.designs.iter() {
+        if let Some(diagnosis) = matcher.diagnose(design) {
+            println!("{design}: impossible");
+            println!(
+                "  longest matchable prefix: {} colors ({})",
+                diagnosis.longest_prefix, &design[..diagnosis.longest_prefix]
+            );
+            println!(
+                "  no towel fits at position {} ({}|{})",
+                diagnosis.stuck_at, &design[..diagnosis.stuck_at], &design[diagnosis.stuck_at..]
+            );
+            continue;
+        }
+        let count = matcher.match_design(design, &BigCount::one()).unwrap();
+        println!("{design}: {count} arrangements");
+        if let Some(fewest) = matcher.fewest_towels(design) {
+            println!("  fewest towels: {} ({})", fewest.len(), fewest.join(" "));
+        }
+        if let Some(most) = matcher.most_towels(design) {
+            println!("  most towels: {} ({})", most.len(), most.join(" "));
+        }
+        for arrangement in matcher.arrangements(design).take(limit) {
+            println!("  {}", arrangement.join(" "));
+        }
+    }
+    Ok(())
 }
 
 struct TowelPuzzle {
@@ -74,45 +159,3 @@ impl FromStr for TowelPuzzle {
         return Ok(TowelPuzzle { towels, designs });
     }
 }
-
-struct TowelMatcher<'a> {
-    matches: HashMap<&'a str, u64>,
-    towels: HashSet<&'a str>,
-}
-
-impl<'a> TowelMatcher<'a> {
-    fn new() -> TowelMatcher<'a> {
-        TowelMatcher {
-            matches: HashMap::new(),
-            towels: HashSet::new(),
-        }
-    }
-
-    fn add_match(&mut self, design: &'a str) {
-        let matches = self.match_design(design);
-        self.matches.insert(design, matches + 1);
-        self.towels.insert(design);
-    }
-
-    fn match_design(&mut self, design: &'a str) -> u64 {
-        if let Some(&result) = self.matches.get(design) {
-            return result;
-        } else if design.len() == 1 {
-            self.matches.insert(&design, 0);
-            return 0;
-        }
-
-        let mut count = 0;
-        for i in 1..design.len() {
-            let prefix = &design[0..i];
-            if self.towels.contains(prefix) {
-                let suffix: &str = &design[i..design.len()];
-                let suffix_count = self.match_design(suffix);
-                count += suffix_count;
-            }
-        }
-
-        self.matches.insert(&design, count);
-        return count;
-    }
-}
@@ -0,0 +1,313 @@
+use super::count::ArrangementCount;
+
+struct TrieNode {
+    children: Vec<(u8, usize)>,
+    towel: Option<usize>,
+}
+
+pub struct TowelMatcher {
+    nodes: Vec<TrieNode>,
+    towels: Vec<String>,
+}
+
+pub struct DesignDiagnosis {
+    pub longest_prefix: usize,
+    pub stuck_at: usize,
+}
+
+impl TowelMatcher {
+    pub fn new() -> TowelMatcher {
+        TowelMatcher {
+            nodes: vec![TrieNode { children: Vec::new(), towel: None }],
+            towels: Vec::new(),
+        }
+    }
+
+    pub fn add_match(&mut self, towel: &str) {
+        let mut node = 0;
+        for &color in towel.as_bytes() {
+            node = match self.child(node, color) {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(TrieNode { children: Vec::new(), towel: None });
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.push((color, child));
+                    child
+                }
+            };
+        }
+        if node != 0 && self.nodes[node].towel.is_none() {
+            self.nodes[node].towel = Some(self.towels.len());
+            self.towels.push(towel.to_string());
+        }
+    }
+
+    fn child(&self, node: usize, color: u8) -> Option<usize> {
+        self.nodes[node].children.iter()
+            .find(|&&(child_color, _)| child_color == color)
+            .map(|&(_, child)| child)
+    }
+
+    // Calls `visit(end, towel)` for every towel that matches the design starting at `start`
+    fn for_each_towel_at(&self, design: &[u8], start: usize, mut visit: impl FnMut(usize, usize)) {
+        let mut node = 0;
+        for (end, &color) in design.iter().enumerate().skip(start) {
+            match self.child(node, color) {
+                Some(child) => node = child,
+                None => return,
+            }
+            if let Some(towel) = self.nodes[node].towel {
+                visit(end + 1, towel);
+            }
+        }
+    }
+
+    // completable[i] tells whether the design can be finished starting from color i
+    fn completable(&self, design: &[u8]) -> Vec<bool> {
+        let mut completable = vec![false; design.len() + 1];
+        completable[design.len()] = true;
+        for start in (0..design.len()).rev() {
+            let mut any = false;
+            self.for_each_towel_at(design, start, |end, _| any |= completable[end]);
+            completable[start] = any;
+        }
+        completable
+    }
+
+    pub fn is_possible(&self, design: &str) -> bool {
+        self.completable(design.as_bytes())[0]
+    }
+
+    // ways[i] is the number of arrangements of the first i colors of the design, None on overflow
+    pub fn match_design<C: ArrangementCount>(&self, design: &str, one: &C) -> Option<C> {
+        let design = design.as_bytes();
+        let mut ways = vec![one.zero_like(); design.len() + 1];
+        ways[0] = one.clone();
+        for start in 0..design.len() {
+            if ways[start].is_zero() {
+                continue;
+            }
+            let count = ways[start].clone();
+            let mut overflow = false;
+            self.for_each_towel_at(design, start, |end, _| {
+                match ways[end].checked_add(&count) {
+                    Some(sum) => ways[end] = sum,
+                    None => overflow = true,
+                }
+            });
+            if overflow {
+                return None;
+            }
+        }
+        ways.pop()
+    }
+
+    pub fn arrangements<'a>(&'a self, design: &str) -> Arrangements<'a> {
+        let design = design.as_bytes().to_vec();
+        let completable = self.completable(&design);
+        let mut arrangements = Arrangements {
+            matcher: self,
+            design,
+            completable,
+            stack: Vec::new(),
+            path: Vec::new(),
+        };
+        if arrangements.completable[0] {
+            let choices = arrangements.choices(0);
+            arrangements.stack.push((0, choices));
+        }
+        arrangements
+    }
+
+    pub fn fewest_towels(&self, design: &str) -> Option<Vec<&str>> {
+        self.extreme_arrangement(design, |candidate, best| candidate < best)
+    }
+
+    pub fn most_towels(&self, design: &str) -> Option<Vec<&str>> {
+        self.extreme_arrangement(design, |candidate, best| candidate > best)
+    }
+
+    // best[i] holds the towel count and the first towel of the preferred way to finish from color i
+    fn extreme_arrangement(&self, design: &str, prefer: impl Fn(usize, usize) -> bool) -> Option<Vec<&str>> {
+        let design = design.as_bytes();
+        let mut best: Vec<Option<(usize, usize, usize)>> = vec![None; design.len() + 1];
+        best[design.len()] = Some((0, design.len(), 0));
+        for start in (0..design.len()).rev() {
+            let mut choice: Option<(usize, usize, usize)> = None;
+            self.for_each_towel_at(design, start, |end, towel| {
+                if let Some((count, _, _)) = best[end] {
+                    if choice.is_none_or(|(best_count, _, _)| prefer(count + 1, best_count)) {
+                        choice = Some((count + 1, end, towel));
+                    }
+                }
+            });
+            best[start] = choice;
+        }
+
+        best[0]?;
+        let mut arrangement = Vec::new();
+        let mut position = 0;
+        while position < design.len() {
+            let (_, end, towel) = best[position].unwrap();
+            arrangement.push(self.towels[towel].as_str());
+            position = end;
+        }
+        Some(arrangement)
+    }
+
+    pub fn diagnose(&self, design: &str) -> Option<DesignDiagnosis> {
+        let design = design.as_bytes();
+        let mut reachable = vec![false; design.len() + 1];
+        reachable[0] = true;
+        let mut stuck_at = None;
+        for start in 0..design.len() {
+            if !reachable[start] {
+                continue;
+            }
+            let mut fits = false;
+            self.for_each_towel_at(design, start, |end, _| {
+                reachable[end] = true;
+                fits = true;
+            });
+            if !fits && stuck_at.is_none() {
+                stuck_at = Some(start);
+            }
+        }
+        if reachable[design.len()] {
+            return None;
+        }
+        let longest_prefix = reachable.iter().rposition(|&reachable| reachable).unwrap();
+        Some(DesignDiagnosis { longest_prefix, stuck_at: stuck_at.unwrap_or(longest_prefix) })
+    }
+}
+
+pub struct Arrangements<'a> {
+    matcher: &'a TowelMatcher,
+    design: Vec<u8>,
+    completable: Vec<bool>,
+    stack: Vec<(usize, Vec<(usize, usize)>)>,
+    path: Vec<usize>,
+}
+
+impl<'a> Arrangements<'a> {
+    // Only towels after which the design can still be finished, in reverse so that popping keeps trie order
+    fn choices(&self, start: usize) -> Vec<(usize, usize)> {
+        let mut choices = Vec::new();
+        self.matcher.for_each_towel_at(&self.design, start, |end, towel| {
+            if self.completable[end] {
+                choices.push((end, towel));
+            }
+        });
+        choices.reverse();
+        choices
+    }
+
+    fn pop_frame(&mut self) {
+        self.stack.pop();
+        if !self.stack.is_empty() {
+            self.path.pop();
+        }
+    }
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (position, choices) = self.stack.last_mut()?;
+            if *position == self.design.len() {
+                let arrangement = self.path.iter().map(|&towel| self.matcher.towels[towel].as_str()).collect();
+                self.pop_frame();
+                return Some(arrangement);
+            }
+            match choices.pop() {
+                Some((end, towel)) => {
+                    self.path.push(towel);
+                    let choices = self.choices(end);
+                    self.stack.push((end, choices));
+                }
+                None => self.pop_frame(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    const COLORS: &[u8] = b"wubrg";
+    const SEED: u64 = 2024;
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        fn stripe(&mut self, colors: usize, max_len: usize) -> String {
+            let len = 1 + self.below(max_len);
+            (0..len).map(|_| COLORS[self.below(colors)] as char).collect()
+        }
+    }
+
+    // Plain recursion over every towel that fits the front of the design, as a reference for the trie matcher
+    fn naive_arrangements(towels: &BTreeSet<String>, design: &str) -> Vec<usize> {
+        if design.is_empty() {
+            return vec![0];
+        }
+        towels.iter()
+            .filter(|towel| design.starts_with(towel.as_str()))
+            .flat_map(|towel| naive_arrangements(towels, &design[towel.len()..]).into_iter().map(|count| count + 1))
+            .collect()
+    }
+
+    #[test]
+    fn matches_naive_arrangements_on_random_cases() {
+        let mut rng = XorShift(SEED);
+        for case in 0..1000 {
+            let colors = 1 + rng.below(COLORS.len());
+            let mut towels: Vec<String> = (0..1 + rng.below(8)).map(|_| rng.stripe(colors, 4)).collect();
+            let designs: Vec<String> = (0..10).map(|_| rng.stripe(colors, 12)).collect();
+            let reference: BTreeSet<String> = towels.iter().cloned().collect();
+
+            let mut forward = TowelMatcher::new();
+            for towel in towels.iter() {
+                forward.add_match(towel);
+            }
+            // Towels come in a different order, some of them twice
+            for i in (1..towels.len()).rev() {
+                towels.swap(i, rng.below(i + 1));
+            }
+            let mut shuffled = TowelMatcher::new();
+            for towel in towels.iter().chain(towels.iter().take(2)) {
+                shuffled.add_match(towel);
+            }
+
+            for design in designs.iter() {
+                let expected = naive_arrangements(&reference, design);
+                let failure = format!("case #{case} (seed {SEED}), design '{design}' with towels {towels:?}");
+                for matcher in [&forward, &shuffled] {
+                    assert_eq!(matcher.match_design(design, &1u64), Some(expected.len() as u64), "{failure}");
+                    assert_eq!(matcher.is_possible(design), !expected.is_empty(), "{failure}");
+                    assert_eq!(matcher.diagnose(design).is_some(), expected.is_empty(), "{failure}");
+                    assert_eq!(matcher.arrangements(design).count(), expected.len(), "{failure}");
+                    let fewest = matcher.fewest_towels(design).map(|arrangement| arrangement.len());
+                    let most = matcher.most_towels(design).map(|arrangement| arrangement.len());
+                    assert_eq!(fewest, expected.iter().copied().min(), "{failure}");
+                    assert_eq!(most, expected.iter().copied().max(), "{failure}");
+                }
+            }
+        }
+    }
+}
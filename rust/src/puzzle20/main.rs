@@ -1,90 +1,133 @@
-use core::{get_data_path, Grid};
-use std::{collections::{HashMap, HashSet}, fs::read_to_string};
+use core::{get_data_path, Grid, split_options};
+use report::ReportOptions;
+use std::{collections::VecDeque, env, fs::{read_to_string, File}, io::{LineWriter, Write}};
+use std::path::PathBuf;
+use std::process::exit;
+use std::thread;
+
+mod report;
+mod scan;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (positional, options) = split_options(
+        &args[1..], &["--max-cheat", "--min-saving", "--threads", "--top", "--csv", "--json"]
+    );
+    let input_path = positional.first()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_data_path("input/puzzle20.txt"));
+    let parse = |name: &str, default: i32| -> i32 {
+        match options.get(name).map(|value| value.parse::<i32>().ok().filter(|&n| n > 0)) {
+            Some(Some(value)) => value,
+            Some(None) => {
+                eprintln!("Error: Invalid {name} value: {}, expected a positive number", options[name]);
+                exit(1);
+            }
+            None => default,
+        }
+    };
+    let max_cheat = parse("--max-cheat", 20);
+    let min_saving = parse("--min-saving", 100);
+    let default_threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let threads = parse("--threads", default_threads as i32) as usize;
+    let report = ReportOptions {
+        print_histogram: options.contains_key("--histogram"),
+        csv_path: options.get("--csv").copied(),
+        json_path: options.get("--json").copied(),
+        top: parse("--top", 0) as usize,
+    };
+    if report.top > 26 {
+        eprintln!("Error: Invalid --top value: {}, at most 26 cheats can be labeled", report.top);
+        exit(1);
+    }
+
     use std::time::Instant;
     let before = Instant::now();
-    basic();
-    advanced();
+    let result = read_to_string(&input_path)
+        .map_err(|err| format!("Failed to read {}: {err}", input_path.display()))
+        .and_then(|input| {
+            basic(&input, min_saving, threads)?;
+            advanced(&input, max_cheat, min_saving, threads, &report)
+        });
+    if let Err(err) = result {
+        eprintln!("Error: {err}");
+        exit(1);
+    }
     println!("Elapsed time: {:.2?}", before.elapsed());
 }
 
-fn basic() {
-    let input = read_to_string(get_data_path("input/puzzle20.txt")).unwrap();
-    let grid = Grid::from_lines(
+fn parse_grid(input: &str) -> Result<Grid<char>, String> {
+    Grid::from_lines(
         &input.lines()
             .filter(|line| !line.is_empty())
             .map(|line| line.into())
             .collect::<Vec<String>>()
-    ).unwrap();
-
-    let track = RaceTrack::compute(&grid).unwrap();
-    let mut cheats = HashSet::new();
-    for from in track.tiles.iter() {
-        let from_ps = track.distances.get(*from).unwrap_or(-1);
-        for cheat_to in track.cheat_neighbors(&from) {
-            let to_ps = track.distances.get(cheat_to).unwrap_or(-1);
-            if to_ps > from_ps {
-                let save_ps = to_ps - from_ps - (cheat_to.0 - from.0).abs() - (cheat_to.1 - from.1).abs();
-                if save_ps > 0 {
-                    cheats.insert((*from, cheat_to, save_ps));
-                }
-            }
-        }
-    }
+    )
+}
 
-    let cheat_by_save = group_cheats_by_saved_time(&cheats);
-    let mut large_cheats_count = 0;
-    for (save_ps, cheats) in cheat_by_save.iter() {
-        if *save_ps >= 100 {
-            large_cheats_count += cheats.len();
-        }
-    }
+fn basic(input: &str, min_saving: i32, threads: usize) -> Result<(), String> {
+    let grid = parse_grid(input)?;
+    let track = RaceTrack::compute(&grid)?;
+    let histogram = scan::savings_histogram(&track, 2, min_saving, threads);
+    let large_cheats_count: u64 = histogram.values().sum();
 
     println!("Large cheats count (basic): {large_cheats_count}");
+    Ok(())
 }
 
-fn advanced() {
-    let input = read_to_string(get_data_path("input/puzzle20.txt")).unwrap();
-    let grid = Grid::from_lines(
-        &input.lines()
-            .filter(|line| !line.is_empty())
-            .map(|line| line.into())
-            .collect::<Vec<String>>()
-    ).unwrap();
-
-    let track = RaceTrack::compute(&grid).unwrap();
-    let mut cheats = HashSet::new();
-    for i in 0..track.tiles.len() {
-        let from = track.tiles[i];
-        let from_ps = track.distances.get(from).unwrap_or(-1);
-        for j in i..track.tiles.len() {
-            let to = track.tiles[j];
-            let to_ps = track.distances.get(to).unwrap_or(-1);
-            if to_ps > from_ps {
-                let distance = (to.0 - from.0).abs() + (to.1 - from.1).abs();
-                let save_ps = to_ps - from_ps - distance;
-                if distance <= 20 && save_ps >= 50 {
-                    cheats.insert((from, to, save_ps));
-                }
-            }
+fn advanced(input: &str, max_cheat: i32, min_saving: i32, threads: usize, report: &ReportOptions) -> Result<(), String> {
+    let grid = parse_grid(input)?;
+    let track = RaceTrack::compute(&grid)?;
+    let histogram = scan::savings_histogram(&track, max_cheat, min_saving, threads);
+    let large_cheats_count: u64 = histogram.values().sum();
+
+    if report.print_histogram {
+        for line in report::histogram_lines(&histogram) {
+            println!("{line}");
         }
     }
+    if let Some(csv_path) = report.csv_path {
+        report::write_csv(csv_path, &histogram)?;
+    }
+    if let Some(json_path) = report.json_path {
+        report::write_json(json_path, max_cheat, min_saving, &histogram)?;
+    }
 
-    let cheat_by_save = group_cheats_by_saved_time(&cheats);
-    let mut large_cheats_count = 0;
-    for (save_ps, cheats) in cheat_by_save.iter() {
-        if *save_ps >= 100 {
-            large_cheats_count += cheats.len();
+    if report.top > 0 {
+        // Only the cheats at least as good as the K-th best one have to be listed
+        let mut remaining = report.top as u64;
+        let threshold = histogram.iter().rev()
+            .find(|&(_, &count)| {
+                remaining = remaining.saturating_sub(count);
+                remaining == 0
+            })
+            .map_or(min_saving, |(&save_ps, _)| save_ps);
+        let mut cheats = scan::cheats_saving_at_least(&track, max_cheat, threshold);
+        cheats.truncate(report.top);
+
+        for (i, ((x, y), (to_x, to_y), save_ps)) in cheats.iter().enumerate() {
+            println!("Cheat {}: {x},{y} -> {to_x},{to_y} saves {save_ps} picoseconds", (b'a' + i as u8) as char);
+        }
+        let io_error = |err: std::io::Error| format!("Failed to write cheat overlay: {err}");
+        let mut overlay_writer = LineWriter::new(
+            File::create(get_data_path("output/puzzle20_cheats.txt")).map_err(io_error)?
+        );
+        for line in report::overlay_lines(&grid, &cheats) {
+            writeln!(overlay_writer, "{line}").map_err(io_error)?;
         }
     }
 
     println!("Large cheats count (advanced): {large_cheats_count}");
+    Ok(())
 }
 
+// Cheat start tile, end tile and the picoseconds it saves
+type Cheat = ((i32, i32), (i32, i32), i32);
+
 struct RaceTrack {
-    distances: Grid<i32>,
-    tiles: Vec<(i32, i32)>,
+    from_start: Grid<i32>,
+    to_end: Grid<i32>,
+    best: i32,
 }
 
 impl RaceTrack {
@@ -92,51 +135,69 @@ impl RaceTrack {
         let start = grid.find(&'S').ok_or("Failed to find track start")?;
         let end = grid.find(&'E').ok_or("Failed to find track end")?;
 
-        let mut distances = Grid::new(grid.width(), grid.height(), -1);
-        let mut tiles= Vec::new();
+        let from_start = Self::distances_from(grid, start);
+        let to_end = Self::distances_from(grid, end);
+        let best = from_start.get(end).unwrap();
+        if best < 0 {
+            return Err("The end of the track cannot be reached from the start".to_string());
+        }
 
-        let mut picoseconds = 0;
-        let mut previous = start;
-        let mut current = start;
-        distances.set(current, picoseconds);
-        tiles.push(current);
+        Ok(RaceTrack { from_start, to_end, best })
+    }
 
-        while current != end {
+    fn distances_from(grid: &Grid<char>, from: (i32, i32)) -> Grid<i32> {
+        let mut distances = Grid::new(grid.width(), grid.height(), -1);
+        distances.set(from, 0);
+        let mut queue = VecDeque::from([from]);
+        while let Some(current) = queue.pop_front() {
+            let picoseconds = distances.get(current).unwrap();
             for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
                 let next = (current.0 + dx, current.1 + dy);
-                if next != previous && grid.get(next).unwrap_or('#') != '#' {
-                    previous = current;
-                    current = next;
-                    break;
+                if grid.get(next).unwrap_or('#') != '#' && distances.get(next) == Some(-1) {
+                    distances.set(next, picoseconds + 1);
+                    queue.push_back(next);
                 }
             }
-            picoseconds += 1;
-            distances.set(current, picoseconds);
-            tiles.push(current);
         }
-
-        return Ok(RaceTrack { distances, tiles });
+        distances
     }
+}
 
-    fn cheat_neighbors(&self, at: &(i32, i32)) -> impl Iterator<Item = (i32, i32)> {
-        let mut locations = Vec::new();
-        for dx in -2..=2i32 {
-            let dy_range = 2 - dx.abs();
-            for dy in -dy_range..=dy_range {
-                let next = (at.0 + dx, at.1 + dy);
-                if self.distances.get(next).unwrap_or(-1) >= 0 {
-                    locations.push(next);
-                }
-            }
-        }
-        return locations.into_iter();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two ways around the first wall, a loop, a dead end next to the end and a walled-off pocket
+    const BRANCHING_TRACK: &str = "\
+#########
+#S...#..#
+#.##.####
+#.......#
+#.#.###.#
+#...#..E#
+#########";
+
+    #[test]
+    fn computes_distances_on_branching_track() {
+        let track = RaceTrack::compute(&parse_grid(BRANCHING_TRACK).unwrap()).unwrap();
+        assert_eq!(track.best, 10);
+        assert_eq!(track.from_start.get((4, 3)), Some(5));
+        assert_eq!(track.from_start.get((3, 5)), Some(6));
+        assert_eq!(track.from_start.get((5, 5)), Some(12));
+        assert_eq!(track.to_end.get((1, 1)), Some(10));
+        assert_eq!(track.to_end.get((4, 1)), Some(7));
+        assert_eq!(track.to_end.get((5, 5)), Some(2));
+        assert_eq!(track.from_start.get((6, 1)), Some(-1));
+        assert_eq!(track.to_end.get((6, 1)), Some(-1));
+        assert_eq!(track.to_end.get((7, 1)), Some(-1));
     }
-}
 
-fn group_cheats_by_saved_time(cheats: &HashSet<((i32, i32), (i32, i32), i32)>) -> HashMap<i32, Vec<((i32, i32), (i32, i32))>> {
-    let mut cheat_by_length: HashMap<i32, Vec<_>> = HashMap::new();
-    for (from, to, save_ps) in cheats.iter() {
-        cheat_by_length.entry(*save_ps).or_default().push((*from, *to));
+    #[test]
+    fn rejects_tracks_without_a_race() {
+        let compute = |input: &str| RaceTrack::compute(&parse_grid(input).unwrap()).err();
+        assert_eq!(compute("#####\n#..E#\n#####"), Some("Failed to find track start".to_string()));
+        assert_eq!(compute("#####\n#S..#\n#####"), Some("Failed to find track end".to_string()));
+        assert_eq!(compute("#####\n#S#E#\n#####"),
+                   Some("The end of the track cannot be reached from the start".to_string()));
     }
-    return cheat_by_length;
 }
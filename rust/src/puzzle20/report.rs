@@ -0,0 +1,134 @@
+use core::Grid;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+
+use super::Cheat;
+
+pub struct ReportOptions<'a> {
+    pub print_histogram: bool,
+    pub csv_path: Option<&'a str>,
+    pub json_path: Option<&'a str>,
+    pub top: usize,
+}
+
+// Worded the same way as the puzzle statement, from the smallest saving up
+pub fn histogram_lines(histogram: &BTreeMap<i32, u64>) -> Vec<String> {
+    histogram.iter()
+        .map(|(save_ps, count)| match count {
+            1 => format!("There is one cheat that saves {save_ps} picoseconds."),
+            _ => format!("There are {count} cheats that save {save_ps} picoseconds."),
+        })
+        .collect()
+}
+
+pub fn write_csv(path: &str, histogram: &BTreeMap<i32, u64>) -> Result<(), String> {
+    let io_error = |err: std::io::Error| format!("Failed to write {path}: {err}");
+    let mut writer = LineWriter::new(File::create(path).map_err(io_error)?);
+    writeln!(writer, "picoseconds,cheats").map_err(io_error)?;
+    for (save_ps, count) in histogram.iter() {
+        writeln!(writer, "{save_ps},{count}").map_err(io_error)?;
+    }
+    Ok(())
+}
+
+pub fn write_json(path: &str, max_cheat: i32, min_saving: i32, histogram: &BTreeMap<i32, u64>) -> Result<(), String> {
+    let io_error = |err: std::io::Error| format!("Failed to write {path}: {err}");
+    let mut writer = LineWriter::new(File::create(path).map_err(io_error)?);
+    writeln!(writer, "{{").map_err(io_error)?;
+    writeln!(writer, "  \"max_cheat\": {max_cheat},").map_err(io_error)?;
+    writeln!(writer, "  \"min_saving\": {min_saving},").map_err(io_error)?;
+    writeln!(writer, "  \"total\": {},", histogram.values().sum::<u64>()).map_err(io_error)?;
+    writeln!(writer, "  \"savings\": [").map_err(io_error)?;
+    for (i, (save_ps, count)) in histogram.iter().enumerate() {
+        let separator = if i + 1 < histogram.len() { "," } else { "" };
+        writeln!(writer, "    {{ \"picoseconds\": {save_ps}, \"cheats\": {count} }}{separator}").map_err(io_error)?;
+    }
+    writeln!(writer, "  ]").map_err(io_error)?;
+    writeln!(writer, "}}").map_err(io_error)?;
+    Ok(())
+}
+
+// Cheat #i starts at its lowercase letter and ends at the uppercase one, the walls it passes
+// through (going horizontally first) are marked with '*'; better cheats are drawn on top
+pub fn overlay_lines(grid: &Grid<char>, cheats: &[Cheat]) -> Vec<String> {
+    let mut overlay = grid.map(|&tile| tile);
+    for (i, &((x, y), (to_x, to_y), _)) in cheats.iter().enumerate().rev() {
+        let across = (x.min(to_x)..=x.max(to_x)).map(|route_x| (route_x, y));
+        let down = (y.min(to_y)..=y.max(to_y)).map(|route_y| (to_x, route_y));
+        for at in across.chain(down) {
+            if grid.get(at) == Some('#') {
+                overlay.set(at, '*');
+            }
+        }
+        let label = b'a' + i as u8;
+        overlay.set((x, y), label as char);
+        overlay.set((to_x, to_y), label.to_ascii_uppercase() as char);
+    }
+    overlay.lines().map(|line| line.trim_end().to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_grid;
+    use crate::scan::cheats_saving_at_least;
+    use crate::scan::tests::{sample_track, SAMPLE_TRACK};
+    use std::fs::{read_to_string, remove_file};
+
+    fn written(name: &str, write: impl FnOnce(&str) -> Result<(), String>) -> String {
+        let path = std::env::temp_dir().join(format!("puzzle20_{}_{name}", std::process::id()));
+        let path = path.to_str().unwrap();
+        write(path).unwrap();
+        let content = read_to_string(path).unwrap();
+        remove_file(path).unwrap();
+        content
+    }
+
+    #[test]
+    fn words_histogram_like_the_puzzle() {
+        let histogram = BTreeMap::from([(2, 14), (64, 1)]);
+        assert_eq!(histogram_lines(&histogram), vec![
+            "There are 14 cheats that save 2 picoseconds.",
+            "There is one cheat that saves 64 picoseconds.",
+        ]);
+        assert!(histogram_lines(&BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn writes_csv_and_json() {
+        let histogram = BTreeMap::from([(50, 32), (76, 3)]);
+        let csv = written("histogram.csv", |path| write_csv(path, &histogram));
+        assert_eq!(csv, "picoseconds,cheats\n50,32\n76,3\n");
+
+        let json = written("histogram.json", |path| write_json(path, 20, 50, &histogram));
+        assert_eq!(json, "\
+{
+  \"max_cheat\": 20,
+  \"min_saving\": 50,
+  \"total\": 35,
+  \"savings\": [
+    { \"picoseconds\": 50, \"cheats\": 32 },
+    { \"picoseconds\": 76, \"cheats\": 3 }
+  ]
+}
+");
+        let json = written("empty.json", |path| write_json(path, 2, 100, &BTreeMap::new()));
+        assert!(json.contains("\"total\": 0,\n  \"savings\": [\n  ]\n"));
+    }
+
+    #[test]
+    fn overlays_best_cheats_on_sample() {
+        let cheats = cheats_saving_at_least(&sample_track(), 2, 38);
+        let lines = overlay_lines(&parse_grid(SAMPLE_TRACK).unwrap(), &cheats);
+        // The 40 picoseconds cheat starts under the 64 picoseconds one
+        assert_eq!(lines[6..10], [
+            "#######.#.###.#",
+            "###..A*ac.#...#",
+            "###.###**##.###",
+            "#...###BC.#...#",
+        ]);
+        assert_eq!(lines.len(), 15);
+        assert_eq!(lines[3], "#S#...#.#.#...#");
+    }
+}
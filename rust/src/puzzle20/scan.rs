@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::thread;
+
+use super::{Cheat, RaceTrack};
+
+// Number of cheats for every saving of at least `min_saving` picoseconds, found by scanning
+// the Manhattan diamond of radius `max_length` around each tile reached from the start
+pub fn savings_histogram(track: &RaceTrack, max_length: i32, min_saving: i32, threads: usize) -> BTreeMap<i32, u64> {
+    // Cheats that save nothing are never counted
+    let min_saving = min_saving.max(1);
+    let height = track.from_start.height();
+    let rows_per_thread = (height as usize).div_ceil(threads.max(1)).max(1) as i32;
+    let counts = thread::scope(|scope| {
+        let handles: Vec<_> = (0..height).step_by(rows_per_thread as usize)
+            .map(|first_row| scope.spawn(move || {
+                let mut counts = vec![0u64; track.best.max(0) as usize + 1];
+                for y in first_row..(first_row + rows_per_thread).min(height) {
+                    scan_row(track, y, max_length, min_saving, &mut counts);
+                }
+                counts
+            }))
+            .collect();
+        handles.into_iter()
+            .map(|handle| handle.join().unwrap())
+            .reduce(|mut total, counts| {
+                total.iter_mut().zip(counts).for_each(|(total, count)| *total += count);
+                total
+            })
+            .unwrap_or_default()
+    });
+
+    counts.into_iter()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .map(|(save_ps, count)| (save_ps as i32, count))
+        .collect()
+}
+
+fn scan_row(track: &RaceTrack, y: i32, max_length: i32, min_saving: i32, counts: &mut [u64]) {
+    for x in 0..track.from_start.width() {
+        for (_, _, save_ps) in diamond_cheats(track, (x, y), max_length, min_saving) {
+            counts[save_ps as usize] += 1;
+        }
+    }
+}
+
+// Cheats starting at `from` that end on the track within the Manhattan diamond of radius
+// `max_length` and save at least `min_saving` picoseconds
+fn diamond_cheats(track: &RaceTrack, from: (i32, i32), max_length: i32, min_saving: i32) -> impl Iterator<Item = Cheat> + '_ {
+    let (x, y) = from;
+    let from_ps = track.from_start.get(from).unwrap_or(-1);
+    // Even the longest possible remaining race can not save enough from here on
+    let reachable = from_ps >= 0 && track.best - from_ps >= min_saving;
+    (-max_length..=max_length)
+        .filter(move |_| reachable)
+        .flat_map(move |dy| {
+            let reach = max_length - dy.abs();
+            (-reach..=reach).map(move |dx| (dx, dy))
+        })
+        .filter_map(move |(dx, dy)| {
+            let to = (x + dx, y + dy);
+            let to_ps = track.to_end.get(to).filter(|&to_ps| to_ps >= 0)?;
+            let save_ps = track.best - (from_ps + dx.abs() + dy.abs() + to_ps);
+            (save_ps >= min_saving).then_some((from, to, save_ps))
+        })
+}
+
+// Every cheat saving at least `min_saving` picoseconds, the best ones first
+pub fn cheats_saving_at_least(track: &RaceTrack, max_length: i32, min_saving: i32) -> Vec<Cheat> {
+    let min_saving = min_saving.max(1);
+    let mut cheats: Vec<Cheat> = (0..track.from_start.height())
+        .flat_map(|y| (0..track.from_start.width()).map(move |x| (x, y)))
+        .flat_map(|from| diamond_cheats(track, from, max_length, min_saving))
+        .collect();
+    cheats.sort_by_key(|&((x, y), (to_x, to_y), save_ps)| (-save_ps, y, x, to_y, to_x));
+    cheats
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::parse_grid;
+
+    pub(crate) const SAMPLE_TRACK: &str = "\
+###############
+#...#...#.....#
+#.#.#.#.#.###.#
+#S#...#.#.#...#
+#######.#.#.###
+#######.#.#...#
+#######.#.###.#
+###..E#...#...#
+###.#######.###
+#...###...#...#
+#.#####.#.###.#
+#.#...#.#.#...#
+#.#.#.#.#.#.###
+#...#...#...###
+###############";
+
+    pub(crate) fn sample_track() -> RaceTrack {
+        RaceTrack::compute(&parse_grid(SAMPLE_TRACK).unwrap()).unwrap()
+    }
+
+    // Checks every pair of tiles on the track, which is slow but obviously right
+    fn reference_histogram(track: &RaceTrack, max_length: i32, min_saving: i32) -> BTreeMap<i32, u64> {
+        let (width, height) = (track.from_start.width(), track.from_start.height());
+        let tiles: Vec<(i32, i32)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&at| track.from_start.get(at).unwrap() >= 0)
+            .collect();
+        let mut histogram = BTreeMap::new();
+        for &from in tiles.iter() {
+            for &to in tiles.iter() {
+                let length = (to.0 - from.0).abs() + (to.1 - from.1).abs();
+                let save_ps = track.best - (track.from_start.get(from).unwrap() + length + track.to_end.get(to).unwrap());
+                if length <= max_length && save_ps >= min_saving {
+                    *histogram.entry(save_ps).or_default() += 1;
+                }
+            }
+        }
+        histogram
+    }
+
+    #[test]
+    fn matches_checking_every_pair_of_tiles() {
+        let track = sample_track();
+        for max_length in 1..=20 {
+            for min_saving in [1, 50] {
+                let expected = reference_histogram(&track, max_length, min_saving);
+                for threads in [1, 4] {
+                    assert_eq!(savings_histogram(&track, max_length, min_saving, threads), expected,
+                               "max cheat {max_length}, min saving {min_saving}, {threads} thread(s)");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn counts_sample_cheats() {
+        let track = sample_track();
+        let histogram = savings_histogram(&track, 2, 1, 2);
+        let expected = [(2, 14), (4, 14), (6, 2), (8, 4), (10, 2), (12, 3), (20, 1), (36, 1), (38, 1), (40, 1), (64, 1)];
+        assert_eq!(histogram, BTreeMap::from(expected));
+
+        let histogram = savings_histogram(&track, 20, 50, 2);
+        assert_eq!(histogram.values().sum::<u64>(), 285);
+        assert_eq!(histogram.first_key_value(), Some((&50, &32)));
+        assert_eq!(histogram.last_key_value(), Some((&76, &3)));
+    }
+
+    #[test]
+    fn lists_best_cheats_first() {
+        let cheats = cheats_saving_at_least(&sample_track(), 2, 38);
+        assert_eq!(cheats.iter().map(|&(_, _, save_ps)| save_ps).collect::<Vec<i32>>(), vec![64, 40, 38]);
+        assert_eq!(cheats[0], ((7, 7), (5, 7), 64));
+    }
+}
@@ -0,0 +1,273 @@
+use core::{AStar, AStarGraph, AStarNode};
+use std::collections::HashMap;
+use std::ops::Add;
+use std::str::Chars;
+
+use super::keypad::{Keypad, DIRECTIONS};
+
+// Press counts stick to u64::MAX instead of wrapping, so that overflows can be reported
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Presses(pub u64);
+
+impl Add for Presses {
+    type Output = Presses;
+
+    fn add(self, other: Presses) -> Presses {
+        Presses(self.0.saturating_add(other.0))
+    }
+}
+
+// Cheapest presses on the steering keypad that move the arm from one button to another
+// and push it, ending with 'A', together with the human presses they cost
+pub type MoveTable = HashMap<(char, char), (String, Presses)>;
+
+pub struct KeypadLayer<'a> {
+    pub keypad: &'a Keypad,
+    pub moves: MoveTable,
+}
+
+pub struct KeypadChain<'a> {
+    pub layers: Vec<KeypadLayer<'a>>,
+}
+
+impl<'a> KeypadChain<'a> {
+    // Layers are solved from the human inward, each one minimizing the cost of the layer steering it
+    pub fn new(keypads: &[&'a Keypad]) -> Result<KeypadChain<'a>, String> {
+        let mut layers: Vec<KeypadLayer<'a>> = Vec::new();
+        for &keypad in keypads.iter().rev() {
+            let steering = layers.last().map(|layer| &layer.moves);
+            let mut moves = HashMap::new();
+            for from in keypad.buttons() {
+                for to in keypad.buttons() {
+                    if let Some((presses, cost)) = cheapest_moves(keypad, steering, from, to) {
+                        if cost.0 == u64::MAX {
+                            return Err(format!("Press count overflows on keypad '{}'", keypad.name));
+                        }
+                        moves.insert((from, to), (presses, cost));
+                    }
+                }
+            }
+            layers.push(KeypadLayer { keypad, moves });
+        }
+        layers.reverse();
+        Ok(KeypadChain { layers })
+    }
+
+    pub fn presses_for(&self, code: &str) -> Result<u64, String> {
+        let layer = &self.layers[0];
+        let mut total = 0u64;
+        let mut previous = 'A';
+        for button in code.chars() {
+            if layer.keypad.position(button).is_none() {
+                return Err(format!("Keypad '{}' has no '{button}' button", layer.keypad.name));
+            }
+            let (_, cost) = layer.moves.get(&(previous, button)).ok_or_else(|| {
+                format!("Button '{button}' can not be reached from '{previous}' on keypad '{}'", layer.keypad.name)
+            })?;
+            total = total.checked_add(cost.0).ok_or_else(|| format!("Press count for code {code} overflows"))?;
+            previous = button;
+        }
+        Ok(total)
+    }
+
+    pub fn presses<'b>(&'b self, code: &'b str) -> Result<PressStream<'b>, String> {
+        // Inner layers only ever use moves that exist, so checking the code is enough
+        self.presses_for(code)?;
+        Ok(PressStream {
+            chain: self,
+            code: code.chars(),
+            arms: vec!['A'; self.layers.len()],
+            stack: Vec::new(),
+        })
+    }
+}
+
+fn steering_cost(steering: Option<&MoveTable>, from: char, to: char) -> Option<Presses> {
+    match steering {
+        Some(moves) => moves.get(&(from, to)).map(|&(_, cost)| cost),
+        None => Some(Presses(1)),
+    }
+}
+
+fn cheapest_moves(keypad: &Keypad, steering: Option<&MoveTable>, from: char, to: char) -> Option<(String, Presses)> {
+    let mut astar = AStar::new(ArmGraph {
+        keypad,
+        steering,
+        from: keypad.position(from)?,
+        to: keypad.position(to)?,
+    });
+    while !astar.next() {}
+
+    let (goal, cost) = astar.found_goal().as_ref()?;
+    let mut presses: Vec<char> = astar.iter_back_path(goal.key())
+        .flat_map(|(_, button)| button.copied())
+        .collect();
+    presses.reverse();
+    Some((presses.into_iter().collect(), *cost))
+}
+
+// The arm position on this keypad, the last button pushed on the steering keypad
+// and whether the button under the arm has been pushed already
+#[derive(Clone, Debug)]
+struct ArmNode {
+    at: (i32, i32),
+    last: char,
+    pushed: bool,
+}
+
+impl AStarNode for ArmNode {
+    type Key = ((i32, i32), char, bool);
+
+    fn key(&self) -> Self::Key {
+        (self.at, self.last, self.pushed)
+    }
+}
+
+struct ArmGraph<'a> {
+    keypad: &'a Keypad,
+    steering: Option<&'a MoveTable>,
+    from: (i32, i32),
+    to: (i32, i32),
+}
+
+impl<'a> AStarGraph<ArmNode> for ArmGraph<'a> {
+    type Edge = char;
+    type Cost = Presses;
+
+    fn start(&self) -> ArmNode {
+        ArmNode { at: self.from, last: 'A', pushed: false }
+    }
+
+    fn neighbors(&self, node: &ArmNode) -> impl Iterator<Item = (ArmNode, char, Presses)> + '_ {
+        let node = node.clone();
+        let steps = DIRECTIONS.into_iter()
+            .filter(move |_| !node.pushed)
+            .filter_map(move |(button, (dx, dy))| {
+                let at = (node.at.0 + dx, node.at.1 + dy);
+                self.keypad.button_at(at)?;
+                let cost = steering_cost(self.steering, node.last, button)?;
+                Some((ArmNode { at, last: button, pushed: false }, button, cost))
+            });
+        let push = Some(node.clone())
+            .filter(|node| !node.pushed && node.at == self.to)
+            .and_then(|node| {
+                let cost = steering_cost(self.steering, node.last, 'A')?;
+                Some((ArmNode { at: node.at, last: 'A', pushed: true }, 'A', cost))
+            });
+        steps.chain(push)
+    }
+
+    fn estimate(&self, _node: &ArmNode) -> Presses {
+        Presses(0)
+    }
+
+    fn is_goal(&self, node: &ArmNode) -> bool {
+        node.pushed
+    }
+}
+
+// Human presses for a code, expanded one at a time through every layer; only the arm
+// positions and one move string per layer are kept, as the full sequence can be huge
+pub struct PressStream<'a> {
+    chain: &'a KeypadChain<'a>,
+    code: Chars<'a>,
+    arms: Vec<char>,
+    stack: Vec<(usize, Chars<'a>)>,
+}
+
+impl<'a> PressStream<'a> {
+    fn push_target(&mut self, layer: usize, button: char) {
+        let (presses, _) = &self.chain.layers[layer].moves[&(self.arms[layer], button)];
+        self.arms[layer] = button;
+        self.stack.push((layer, presses.chars()));
+    }
+}
+
+impl<'a> Iterator for PressStream<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            match self.stack.last_mut() {
+                None => {
+                    let button = self.code.next()?;
+                    self.push_target(0, button);
+                }
+                Some((layer, presses)) => match presses.next() {
+                    None => {
+                        self.stack.pop();
+                    }
+                    Some(press) if *layer + 1 == self.chain.layers.len() => return Some(press),
+                    Some(press) => {
+                        let layer = *layer + 1;
+                        self.push_target(layer, press);
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypad::{default_keypads, parse_chain, parse_layouts};
+    use crate::numeric_part;
+    use crate::simulate::simulate;
+
+    const SAMPLE_CODES: [&str; 5] = ["029A", "980A", "179A", "456A", "379A"];
+
+    fn complexity(spec: &str) -> u64 {
+        let keypads = default_keypads();
+        let chain = KeypadChain::new(&parse_chain(spec, &keypads).unwrap()).unwrap();
+        SAMPLE_CODES.iter().map(|code| chain.presses_for(code).unwrap() * numeric_part(code)).sum()
+    }
+
+    #[test]
+    fn computes_sample_complexity() {
+        assert_eq!(complexity("numeric,directional*2"), 126384);
+        assert_eq!(complexity("numeric,directional*25"), 154115708116294);
+    }
+
+    // The streamed presses must type the code without pointing at a gap, and there must be
+    // exactly as many as were counted
+    #[test]
+    fn replays_streamed_presses_to_the_code() {
+        let keypads = default_keypads();
+        for robots in 0..=6 {
+            let chain_keypads = parse_chain(&format!("numeric,directional*{robots}"), &keypads).unwrap();
+            let chain = KeypadChain::new(&chain_keypads).unwrap();
+            for code in SAMPLE_CODES {
+                let mut count = 0u64;
+                let typed = simulate(&chain_keypads, chain.presses(code).unwrap().inspect(|_| count += 1));
+                assert_eq!(typed, Ok(code.to_string()), "{robots} robot(s)");
+                assert_eq!(count, chain.presses_for(code).unwrap(), "{robots} robot(s), code {code}");
+            }
+        }
+    }
+
+    #[test]
+    fn solves_custom_heterogeneous_chain() {
+        let mut keypads = default_keypads();
+        parse_layouts("pin:\n12\n3A\n\ncross:\n ^ \n<A>\n v \n", &mut keypads).unwrap();
+
+        // Pushing '3' then 'A' on the pin pad takes "<A" then ">A" on the keypad steering it
+        let alone = KeypadChain::new(&parse_chain("pin", &keypads).unwrap()).unwrap();
+        assert_eq!(alone.presses_for("3A"), Ok(4));
+        let crossed = KeypadChain::new(&parse_chain("pin,cross", &keypads).unwrap()).unwrap();
+        assert_eq!(crossed.presses_for("3A"), Ok(8));
+        let directed = KeypadChain::new(&parse_chain("pin,directional", &keypads).unwrap()).unwrap();
+        assert_eq!(directed.presses_for("3A"), Ok(12));
+
+        let chain_keypads = parse_chain("pin,cross,directional,cross", &keypads).unwrap();
+        let chain = KeypadChain::new(&chain_keypads).unwrap();
+        assert_eq!(chain.layers.iter().map(|layer| layer.keypad.name.as_str()).collect::<Vec<_>>(),
+                   vec!["pin", "cross", "directional", "cross"]);
+        for code in ["3A", "21A", "1A3A2A"] {
+            let presses: String = chain.presses(code).unwrap().collect();
+            assert_eq!(presses.len() as u64, chain.presses_for(code).unwrap());
+            assert_eq!(simulate(&chain_keypads, presses.chars()), Ok(code.to_string()));
+        }
+        assert_eq!(chain.presses_for("4A").err(), Some("Keypad 'pin' has no '4' button".to_string()));
+    }
+}
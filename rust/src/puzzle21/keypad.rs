@@ -0,0 +1,183 @@
+use core::Grid;
+use std::collections::HashMap;
+
+pub const NUMERIC_LAYOUT: &str = "789\n456\n123\n 0A";
+pub const DIRECTIONAL_LAYOUT: &str = " ^A\n<v>";
+
+// Buttons a keypad must have to steer the robot arm in front of the previous keypad
+pub const DIRECTIONS: [(char, (i32, i32)); 4] = [('^', (0, -1)), ('v', (0, 1)), ('<', (-1, 0)), ('>', (1, 0))];
+
+pub struct Keypad {
+    pub name: String,
+    pub grid: Grid<char>,
+    buttons: HashMap<char, (i32, i32)>,
+}
+
+impl Keypad {
+    // One row of buttons per line, spaces are gaps the robot arm must never point at
+    pub fn parse(name: &str, layout: &str) -> Result<Keypad, String> {
+        let rows: Vec<&str> = layout.lines().filter(|line| !line.trim().is_empty()).collect();
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        if width == 0 {
+            return Err(format!("Keypad '{name}' has no buttons"));
+        }
+
+        let mut grid = Grid::new(width as i32, rows.len() as i32, ' ');
+        let mut buttons = HashMap::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, button) in row.chars().enumerate() {
+                if button == ' ' {
+                    continue;
+                }
+                if buttons.insert(button, (x as i32, y as i32)).is_some() {
+                    return Err(format!("Keypad '{name}' has more than one '{button}' button"));
+                }
+                grid.set((x as i32, y as i32), button);
+            }
+        }
+        if !buttons.contains_key(&'A') {
+            return Err(format!("Keypad '{name}' has no 'A' button for the robot arm to start at"));
+        }
+        Ok(Keypad { name: name.to_string(), grid, buttons })
+    }
+
+    pub fn position(&self, button: char) -> Option<(i32, i32)> {
+        self.buttons.get(&button).copied()
+    }
+
+    pub fn buttons(&self) -> impl Iterator<Item = char> + '_ {
+        self.buttons.keys().copied()
+    }
+
+    pub fn button_at(&self, at: (i32, i32)) -> Option<char> {
+        self.grid.get(at).filter(|&button| button != ' ')
+    }
+
+    pub fn can_steer(&self) -> bool {
+        DIRECTIONS.iter().all(|(button, _)| self.buttons.contains_key(button))
+    }
+}
+
+pub fn default_keypads() -> HashMap<String, Keypad> {
+    [("numeric", NUMERIC_LAYOUT), ("directional", DIRECTIONAL_LAYOUT)].into_iter()
+        .map(|(name, layout)| (name.to_string(), Keypad::parse(name, layout).unwrap()))
+        .collect()
+}
+
+// Layout files hold "name:" header lines, each followed by the rows of that keypad
+pub fn parse_layouts(input: &str, keypads: &mut HashMap<String, Keypad>) -> Result<(), String> {
+    let mut sections: Vec<(usize, &str, String)> = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if let Some(name) = line.trim().strip_suffix(':').filter(|_| !line.starts_with(' ')) {
+            sections.push((i + 1, name.trim(), String::new()));
+        } else if let Some((_, _, layout)) = sections.last_mut() {
+            layout.push_str(line);
+            layout.push('\n');
+        } else if !line.trim().is_empty() {
+            return Err(format!("Line {}: expected a 'name:' header before the keypad rows", i + 1));
+        }
+    }
+    for (line, name, layout) in sections {
+        let keypad = Keypad::parse(name, &layout).map_err(|err| format!("Line {line}: {err}"))?;
+        keypads.insert(name.to_string(), keypad);
+    }
+    Ok(())
+}
+
+// A chain such as "numeric,directional*25" lists the keypads from the door inward, each one
+// typed on by a robot steered from the next one; the last keypad is steered by a human
+pub fn parse_chain<'a>(spec: &str, keypads: &'a HashMap<String, Keypad>) -> Result<Vec<&'a Keypad>, String> {
+    let mut chain = Vec::new();
+    for part in spec.split(',').map(|part| part.trim()) {
+        let (name, repeat) = match part.split_once('*') {
+            Some((name, repeat)) => {
+                let repeat = repeat.trim().parse::<usize>()
+                    .map_err(|_| format!("Invalid repeat count in '{part}'"))?;
+                (name.trim(), repeat)
+            }
+            None => (part, 1),
+        };
+        let keypad = keypads.get(name).ok_or_else(|| format!("Unknown keypad '{name}' in chain '{spec}'"))?;
+        chain.extend(std::iter::repeat_n(keypad, repeat));
+    }
+    if chain.is_empty() {
+        return Err(format!("Chain '{spec}' has no keypads"));
+    }
+    if let Some(keypad) = chain.iter().skip(1).find(|keypad| !keypad.can_steer()) {
+        return Err(format!("Keypad '{}' lacks one of the ^v<>A buttons needed to steer a robot", keypad.name));
+    }
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM_LAYOUTS: &str = "pin:\n12\n3A\n\ncross:\n ^ \n<A>\n v \n";
+
+    fn layout_error(input: &str) -> String {
+        parse_layouts(input, &mut default_keypads()).unwrap_err()
+    }
+
+    fn chain_names(spec: &str, keypads: &HashMap<String, Keypad>) -> Result<Vec<String>, String> {
+        parse_chain(spec, keypads).map(|chain| chain.iter().map(|keypad| keypad.name.clone()).collect())
+    }
+
+    #[test]
+    fn parses_layout_sections() {
+        let mut keypads = default_keypads();
+        parse_layouts(CUSTOM_LAYOUTS, &mut keypads).unwrap();
+        assert_eq!(keypads.len(), 4);
+
+        let pin = &keypads["pin"];
+        assert_eq!((pin.position('1'), pin.position('A')), (Some((0, 0)), Some((1, 1))));
+        let cross = &keypads["cross"];
+        assert_eq!((cross.position('A'), cross.position('v')), (Some((1, 1)), Some((1, 2))));
+        assert_eq!((cross.button_at((0, 0)), cross.button_at((0, 1))), (None, Some('<')));
+        assert!(cross.can_steer() && !pin.can_steer());
+
+        // A section replaces the default keypad of the same name
+        parse_layouts("numeric:\n0A\n", &mut keypads).unwrap();
+        assert_eq!(keypads["numeric"].position('9'), None);
+    }
+
+    #[test]
+    fn reports_layout_errors() {
+        assert_eq!(layout_error("pad:\n1A1\n"), "Line 1: Keypad 'pad' has more than one '1' button");
+        assert_eq!(layout_error("ok:\n1A\npad:\n12\n34\n"),
+                   "Line 3: Keypad 'pad' has no 'A' button for the robot arm to start at");
+        assert_eq!(layout_error("\n12\npad:\n1A\n"), "Line 2: expected a 'name:' header before the keypad rows");
+        assert_eq!(layout_error("pad:\n\n"), "Line 1: Keypad 'pad' has no buttons");
+    }
+
+    #[test]
+    fn parses_chain_specs() {
+        let mut keypads = default_keypads();
+        parse_layouts(CUSTOM_LAYOUTS, &mut keypads).unwrap();
+
+        let names = chain_names("numeric,directional*25", &keypads).unwrap();
+        assert_eq!(names.len(), 26);
+        assert!(names[0] == "numeric" && names[1..].iter().all(|name| name == "directional"));
+        assert_eq!(chain_names(" pin , cross * 2,directional", &keypads).unwrap(),
+                   vec!["pin", "cross", "cross", "directional"]);
+
+        assert_eq!(chain_names("numeric,directional*x", &keypads).err(),
+                   Some("Invalid repeat count in 'directional*x'".to_string()));
+        assert_eq!(chain_names("numeric,arrows", &keypads).err(),
+                   Some("Unknown keypad 'arrows' in chain 'numeric,arrows'".to_string()));
+        assert_eq!(chain_names("numeric*0", &keypads).err(), Some("Chain 'numeric*0' has no keypads".to_string()));
+    }
+
+    #[test]
+    fn requires_steering_keypads_after_the_first() {
+        let mut keypads = default_keypads();
+        parse_layouts(CUSTOM_LAYOUTS, &mut keypads).unwrap();
+
+        assert!(chain_names("pin", &keypads).is_ok());
+        assert!(chain_names("pin,cross", &keypads).is_ok());
+        assert_eq!(chain_names("directional,numeric", &keypads).err(),
+                   Some("Keypad 'numeric' lacks one of the ^v<>A buttons needed to steer a robot".to_string()));
+        assert_eq!(chain_names("numeric,cross,pin*2", &keypads).err(),
+                   Some("Keypad 'pin' lacks one of the ^v<>A buttons needed to steer a robot".to_string()));
+    }
+}
@@ -0,0 +1,79 @@
+use super::keypad::{Keypad, DIRECTIONS};
+
+// Replays human presses through the robots in front of every keypad of the chain, the last
+// keypad being the one steered by the human, and returns the buttons pushed on the first one
+pub fn simulate(keypads: &[&Keypad], presses: impl Iterator<Item = char>) -> Result<String, String> {
+    let mut arms: Vec<(i32, i32)> = keypads.iter()
+        .map(|keypad| keypad.position('A').unwrap())
+        .collect();
+    let mut typed = String::new();
+    for (index, press) in presses.enumerate() {
+        let mut layer = keypads.len() - 1;
+        let mut button = press;
+        loop {
+            if button == 'A' {
+                let pushed = keypads[layer].button_at(arms[layer]).unwrap();
+                if layer == 0 {
+                    typed.push(pushed);
+                    break;
+                }
+                button = pushed;
+                layer -= 1;
+                continue;
+            }
+
+            let keypad = keypads[layer];
+            let Some(&(_, (dx, dy))) = DIRECTIONS.iter().find(|&&(direction, _)| direction == button) else {
+                return Err(format!(
+                    "Press #{}: '{button}' reaches the robot at keypad '{}' (#{}) which only understands ^v<>A",
+                    index + 1, keypad.name, layer + 1
+                ));
+            };
+            let at = (arms[layer].0 + dx, arms[layer].1 + dy);
+            if keypad.button_at(at).is_none() {
+                return Err(format!(
+                    "Press #{}: the robot arm at keypad '{}' (#{}) points at a gap",
+                    index + 1, keypad.name, layer + 1
+                ));
+            }
+            arms[layer] = at;
+            break;
+        }
+    }
+    Ok(typed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypad::default_keypads;
+
+    #[test]
+    fn types_the_sample_code() {
+        let keypads = default_keypads();
+        assert_eq!(simulate(&[&keypads["numeric"]], "<A^A>^^AvvvA".chars()), Ok("029A".to_string()));
+        let chain = [&keypads["numeric"], &keypads["directional"]];
+        assert_eq!(simulate(&chain, "v<<A>>^A<A>AvA<^AA>A<vAAA>^A".chars()), Ok("029A".to_string()));
+        assert_eq!(simulate(&chain, "".chars()), Ok(String::new()));
+    }
+
+    #[test]
+    fn reports_arm_pointing_at_a_gap() {
+        let keypads = default_keypads();
+        assert_eq!(simulate(&[&keypads["numeric"]], "<A<".chars()),
+                   Err("Press #3: the robot arm at keypad 'numeric' (#1) points at a gap".to_string()));
+        let chain = [&keypads["numeric"], &keypads["directional"]];
+        assert_eq!(simulate(&chain, "<<".chars()),
+                   Err("Press #2: the robot arm at keypad 'directional' (#2) points at a gap".to_string()));
+    }
+
+    #[test]
+    fn reports_buttons_that_are_not_directions() {
+        let keypads = default_keypads();
+        let chain = [&keypads["numeric"], &keypads["numeric"]];
+        assert_eq!(simulate(&chain, "<A".chars()),
+                   Err("Press #2: '0' reaches the robot at keypad 'numeric' (#1) which only understands ^v<>A".to_string()));
+        assert_eq!(simulate(&[&keypads["directional"]], "vA9".chars()),
+                   Err("Press #3: '9' reaches the robot at keypad 'directional' (#1) which only understands ^v<>A".to_string()));
+    }
+}